@@ -14,6 +14,21 @@ fn new_error(msg: String) -> PyErr {
     PyErr::new::<PyTypeError, _>(msg)
 }
 
+/// Convert to `LineString`s keeping the index of null geometries
+///
+/// Null geometries become empty `LineString`s which are never matched.
+pub fn to_line_strings(x: &LineStringArray) -> PyResult<Vec<geo_types::LineString>> {
+    use geoarrow_array::GeoArrowArrayAccessor;
+    x.iter()
+        .map(|xi| match xi {
+            Some(xi) => xi
+                .map(|xi| xi.to_line_string())
+                .map_err(|e| new_error(e.to_string())),
+            None => Ok(geo_types::LineString::new(vec![])),
+        })
+        .collect()
+}
+
 pub fn as_geoarrow_lines(x: PyArray) -> PyResult<LineStringArray> {
     let (array, field) = x.into_inner();
     let res =
@@ -38,16 +53,18 @@ impl PyAnime {
         distance_tolerance: f64,
         angle_tolerance: f64,
     ) -> PyResult<Self> {
-        use geoarrow_array::GeoArrowArrayAccessor;
-        let source = as_geoarrow_lines(source)?;
-        let target = as_geoarrow_lines(target)?;
+        let source = to_line_strings(&as_geoarrow_lines(source)?)?;
+        let target = to_line_strings(&as_geoarrow_lines(target)?)?;
 
-        let res = Anime::new(
-            source.iter_values().map(|xi| xi.unwrap().to_line_string()),
-            target.iter_values().map(|xi| xi.unwrap().to_line_string()),
+        let mut res = Anime::load_geometries(
+            source.into_iter(),
+            target.into_iter(),
             distance_tolerance,
             angle_tolerance,
         );
+        // the offset and angle of each match feed its confidence
+        res.record_segments = true;
+        res.find_matches().map_err(|e| new_error(e.to_string()))?;
         Ok(Self(res))
     }
 
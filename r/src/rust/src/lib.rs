@@ -63,13 +63,22 @@ fn init_anime(
 ) -> ExternalPtr<anime::Anime> {
     let source = read_geoarrow_r(source).unwrap().clone();
     let target = read_geoarrow_r(target).unwrap().clone();
+    // null geometries are kept as empty linestrings which are never matched
     let mut anime = anime::Anime::load_geometries(
-        source.iter_values().map(|x| x.unwrap().to_line_string()),
-        target.iter_values().map(|x| x.unwrap().to_line_string()),
+        source.iter().map(|x| match x {
+            Some(x) => x.unwrap().to_line_string(),
+            None => geo::LineString::new(vec![]),
+        }),
+        target.iter().map(|x| match x {
+            Some(x) => x.unwrap().to_line_string(),
+            None => geo::LineString::new(vec![]),
+        }),
         distance_tolerance,
         angle_tolerance,
     );
 
+    // the offset and angle of each match feed its confidence
+    anime.record_segments = true;
     anime.find_matches().unwrap();
 
     let mut ptr = ExternalPtr::new(anime);
@@ -128,6 +137,8 @@ struct MatchRow {
     shared_len: f64,
     source_weighted: f64,
     target_weighted: f64,
+    aligned: bool,
+    confidence: f64,
 }
 
 #[extendr]
@@ -148,6 +159,8 @@ fn get_matches_(anime: ExternalPtr<Anime>) -> Robj {
                     shared_len: ci.shared_len,
                     source_weighted: ci.shared_len / source_len,
                     target_weighted: ci.shared_len / target_len,
+                    aligned: ci.aligned_len >= ci.shared_len - ci.aligned_len,
+                    confidence: anime.confidence(*idx, ci),
                 }
             })
         })
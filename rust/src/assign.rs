@@ -0,0 +1,402 @@
+use crate::{Anime, AnimeError};
+use arrow::array::{Float64Array, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// The largest cost matrix, in cells, solved with the Hungarian algorithm
+///
+/// A connected component of the match graph with `n` sources and `m`
+/// targets needs an `n` by `m` matrix and `O(n² m)` time. Larger
+/// components are assigned greedily instead.
+pub const MAX_OPTIMAL_CELLS: usize = 1 << 22;
+
+/// How matches are reduced to an assignment
+///
+/// Every method maximises the total `shared_len` of the assigned pairs.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AssignmentMethod {
+    /// Optimal 1:1 assignment solved with the Hungarian algorithm
+    ///
+    /// Each connected component of the match graph is solved separately.
+    /// Components whose cost matrix exceeds [`MAX_OPTIMAL_CELLS`] fall back
+    /// to the greedy 1:1 assignment.
+    #[default]
+    Optimal,
+    /// Greedy 1:1 assignment taking pairs in descending `shared_len`
+    Greedy,
+    /// Each source is assigned its best target. A target can be
+    /// assigned to many sources.
+    GreedyOneToMany,
+}
+
+/// A (source, target) pair selected by an assignment
+pub type AssignedPair = (usize, usize, f64);
+
+impl Anime {
+    /// Reduce the matches to an assignment
+    ///
+    /// Returns `(source_index, target_index, shared_len)` tuples ordered by
+    /// source then target. Each source appears at most once. With
+    /// [`AssignmentMethod::Optimal`] and [`AssignmentMethod::Greedy`] each
+    /// target also appears at most once.
+    pub fn assign(&self, method: AssignmentMethod) -> Result<Vec<AssignedPair>, AnimeError> {
+        let inner = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+
+        let edges = inner
+            .iter()
+            .flat_map(|(j, items)| {
+                items
+                    .iter()
+                    .filter(|mc| mc.shared_len > 0.0)
+                    .map(move |mc| (mc.source_index, *j, mc.shared_len))
+            })
+            .collect::<Vec<_>>();
+
+        let mut res = match method {
+            AssignmentMethod::Optimal => self.assign_optimal(&edges),
+            AssignmentMethod::Greedy => assign_greedy(edges, true),
+            AssignmentMethod::GreedyOneToMany => assign_greedy(edges, false),
+        };
+        res.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+        Ok(res)
+    }
+
+    /// Reduce the matches to a crosswalk `RecordBatch`
+    ///
+    /// Contains the columns `source_id`, `target_id`, and `shared_len`. The
+    /// assigned pairs are followed by unmatched sources, with a null
+    /// `target_id`, and then unmatched targets, with a null `source_id`.
+    /// `shared_len` is null for unmatched features.
+    pub fn crosswalk(&self, method: AssignmentMethod) -> Result<RecordBatch, AnimeError> {
+        let pairs = self.assign(method)?;
+
+        let mut source_matched = vec![false; self.source_lens.len()];
+        let mut target_matched = vec![false; self.target_lens.len()];
+        for (i, j, _) in pairs.iter() {
+            source_matched[*i] = true;
+            target_matched[*j] = true;
+        }
+
+        let n = pairs.len()
+            + source_matched.iter().filter(|m| !**m).count()
+            + target_matched.iter().filter(|m| !**m).count();
+        let mut source_res = Int32Array::builder(n);
+        let mut target_res = Int32Array::builder(n);
+        let mut shared_len_res = Float64Array::builder(n);
+
+        for (i, j, shared_len) in pairs {
+            source_res.append_value(i as i32);
+            target_res.append_value(j as i32);
+            shared_len_res.append_value(shared_len);
+        }
+        for (i, _) in source_matched.iter().enumerate().filter(|(_, m)| !**m) {
+            source_res.append_value(i as i32);
+            target_res.append_null();
+            shared_len_res.append_null();
+        }
+        for (j, _) in target_matched.iter().enumerate().filter(|(_, m)| !**m) {
+            source_res.append_null();
+            target_res.append_value(j as i32);
+            shared_len_res.append_null();
+        }
+
+        let schema = Schema::new(vec![
+            Field::new("source_id", DataType::Int32, true),
+            Field::new("target_id", DataType::Int32, true),
+            Field::new("shared_len", DataType::Float64, true),
+        ]);
+
+        let res = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(source_res.finish()),
+                Arc::new(target_res.finish()),
+                Arc::new(shared_len_res.finish()),
+            ],
+        )
+        .expect("All arrays should be identical lengths");
+        Ok(res)
+    }
+
+    // solve each connected component of the match graph separately
+    // to keep the cost matrices small
+    fn assign_optimal(&self, edges: &[AssignedPair]) -> Vec<AssignedPair> {
+        let n_source = self.source_lens.len();
+        let mut parent = (0..n_source + self.target_lens.len()).collect::<Vec<_>>();
+        for (i, j, _) in edges {
+            let a = find(&mut parent, *i);
+            let b = find(&mut parent, n_source + j);
+            parent[a] = b;
+        }
+
+        let mut components: BTreeMap<usize, Vec<AssignedPair>> = BTreeMap::new();
+        for edge in edges {
+            let root = find(&mut parent, edge.0);
+            components.entry(root).or_default().push(*edge);
+        }
+
+        components
+            .into_values()
+            .flat_map(|component| solve_component(component, MAX_OPTIMAL_CELLS))
+            .collect()
+    }
+}
+
+pub(crate) fn find(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+pub(crate) fn assign_greedy(mut edges: Vec<AssignedPair>, one_to_one: bool) -> Vec<AssignedPair> {
+    edges.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let mut sources = std::collections::BTreeSet::new();
+    let mut targets = std::collections::BTreeSet::new();
+    edges
+        .into_iter()
+        .filter(|(i, j, _)| {
+            if sources.contains(i) || (one_to_one && targets.contains(j)) {
+                return false;
+            }
+            sources.insert(*i);
+            targets.insert(*j);
+            true
+        })
+        .collect()
+}
+
+// maximise the total shared length of a single connected component,
+// greedily when its cost matrix would have more than `max_cells` cells
+fn solve_component(edges: Vec<AssignedPair>, max_cells: usize) -> Vec<AssignedPair> {
+    let mut sources = edges.iter().map(|e| e.0).collect::<Vec<_>>();
+    let mut targets = edges.iter().map(|e| e.1).collect::<Vec<_>>();
+    sources.sort_unstable();
+    sources.dedup();
+    targets.sort_unstable();
+    targets.dedup();
+
+    let is_dense = sources.len().saturating_mul(targets.len()) <= max_cells;
+    if !is_dense {
+        return assign_greedy(edges, true);
+    }
+
+    // the Hungarian algorithm requires no more rows than columns
+    let transpose = sources.len() > targets.len();
+    let (n_rows, n_cols) = if transpose {
+        (targets.len(), sources.len())
+    } else {
+        (sources.len(), targets.len())
+    };
+
+    // missing edges cost nothing and are dropped afterwards
+    let mut cost = vec![vec![0.0; n_cols]; n_rows];
+    for (i, j, shared_len) in edges.iter() {
+        let si = sources.binary_search(i).unwrap();
+        let tj = targets.binary_search(j).unwrap();
+        let (r, c) = if transpose { (tj, si) } else { (si, tj) };
+        cost[r][c] = -shared_len;
+    }
+
+    hungarian(&cost)
+        .into_iter()
+        .enumerate()
+        .filter(|(r, c)| cost[*r][*c] < 0.0)
+        .map(|(r, c)| {
+            let (si, tj) = if transpose { (c, r) } else { (r, c) };
+            (sources[si], targets[tj], -cost[r][c])
+        })
+        .collect()
+}
+
+/// Minimum cost assignment of each row to a distinct column
+///
+/// Requires no more rows than columns. Returns the column of each row.
+fn hungarian(cost: &[Vec<f64>]) -> Vec<usize> {
+    let n = cost.len();
+    let m = cost.first().map_or(0, |r| r.len());
+
+    // 1-indexed potentials and matching with column 0 as a sentinel
+    let mut u = vec![0.0; n + 1];
+    let mut v = vec![0.0; m + 1];
+    let mut p = vec![0; m + 1];
+    let mut way = vec![0; m + 1];
+
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        let mut minv = vec![f64::INFINITY; m + 1];
+        let mut used = vec![false; m + 1];
+
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+            for j in 1..=m {
+                if !used[j] {
+                    let cur = cost[i0 - 1][j - 1] - u[i0] - v[j];
+                    if cur < minv[j] {
+                        minv[j] = cur;
+                        way[j] = j0;
+                    }
+                    if minv[j] < delta {
+                        delta = minv[j];
+                        j1 = j;
+                    }
+                }
+            }
+            for j in 0..=m {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut res = vec![0; n];
+    for j in 1..=m {
+        if p[j] != 0 {
+            res[p[j] - 1] = j - 1;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MatchCandidate;
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::Int32Type;
+
+    // an Anime with a hand-built match table
+    fn create_anime(n_source: usize, n_target: usize, edges: &[AssignedPair]) -> Anime {
+        let lines = |n: usize| {
+            (0..n)
+                .map(|i| {
+                    let y = i as f64 * 100.0;
+                    geo_types::LineString::from(vec![(0.0, y), (10.0, y)])
+                })
+                .collect::<Vec<_>>()
+        };
+        let anime = Anime::load_geometries(
+            lines(n_source).into_iter(),
+            lines(n_target).into_iter(),
+            1.0,
+            5.0,
+        );
+
+        let mut matches = crate::MatchesMap::new();
+        for (i, j, shared_len) in edges {
+            matches.entry(*j).or_default().push(MatchCandidate {
+                source_index: *i,
+                shared_len: *shared_len,
+                aligned_len: *shared_len,
+                segments: vec![],
+            });
+        }
+        anime.matches.set(matches).unwrap();
+        anime
+    }
+
+    #[test]
+    fn test_hungarian() {
+        let cost = vec![
+            vec![4.0, 1.0, 3.0],
+            vec![2.0, 0.0, 5.0],
+            vec![3.0, 2.0, 2.0],
+        ];
+        // rows 0, 1, 2 to columns 1, 0, 2 costs 5
+        assert_eq!(hungarian(&cost), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn test_optimal_beats_greedy() {
+        // greedy takes (0, 0) and leaves source 1 and target 1 unmatched
+        let edges = [(0, 0, 10.0), (0, 1, 9.0), (1, 0, 8.0)];
+        let anime = create_anime(2, 2, &edges);
+
+        let greedy = anime.assign(AssignmentMethod::Greedy).unwrap();
+        assert_eq!(greedy, vec![(0, 0, 10.0)]);
+
+        let optimal = anime.assign(AssignmentMethod::Optimal).unwrap();
+        assert_eq!(optimal, vec![(0, 1, 9.0), (1, 0, 8.0)]);
+    }
+
+    #[test]
+    fn test_large_component_greedy() {
+        let edges = vec![(0, 0, 10.0), (0, 1, 9.0), (1, 0, 8.0)];
+        assert_eq!(
+            solve_component(edges.clone(), 4),
+            vec![(0, 1, 9.0), (1, 0, 8.0)]
+        );
+        // a 2 by 2 matrix is too large so the component is assigned greedily
+        assert_eq!(solve_component(edges, 3), vec![(0, 0, 10.0)]);
+    }
+
+    #[test]
+    fn test_optimal_more_sources_than_targets() {
+        let edges = [(0, 0, 5.0), (1, 0, 7.0), (2, 0, 1.0), (2, 1, 2.0)];
+        let anime = create_anime(3, 2, &edges);
+
+        let optimal = anime.assign(AssignmentMethod::Optimal).unwrap();
+        assert_eq!(optimal, vec![(1, 0, 7.0), (2, 1, 2.0)]);
+    }
+
+    #[test]
+    fn test_greedy_one_to_many() {
+        let edges = [(0, 0, 10.0), (1, 0, 8.0), (1, 1, 2.0)];
+        let anime = create_anime(2, 2, &edges);
+
+        let res = anime.assign(AssignmentMethod::GreedyOneToMany).unwrap();
+        assert_eq!(res, vec![(0, 0, 10.0), (1, 0, 8.0)]);
+    }
+
+    #[test]
+    fn test_crosswalk_includes_unmatched() {
+        let edges = [(0, 0, 10.0), (1, 0, 8.0)];
+        let anime = create_anime(3, 2, &edges);
+
+        let res = anime.crosswalk(AssignmentMethod::Optimal).unwrap();
+        // one pair, sources 1 and 2 unmatched, target 1 unmatched
+        assert_eq!(res.num_rows(), 4);
+
+        let source_id = res.column(0).as_primitive::<Int32Type>();
+        let target_id = res.column(1).as_primitive::<Int32Type>();
+        assert_eq!(source_id.value(0), 0);
+        assert_eq!(target_id.value(0), 0);
+        assert_eq!(source_id.value(1), 1);
+        assert!(target_id.is_null(1));
+        assert!(source_id.is_null(3));
+        assert_eq!(target_id.value(3), 1);
+    }
+
+    #[test]
+    fn test_assign_requires_matches() {
+        let anime = Anime::load_geometries(std::iter::empty(), std::iter::empty(), 1.0, 5.0);
+        assert!(matches!(
+            anime.assign(AssignmentMethod::Greedy),
+            Err(AnimeError::MatchesNotFound)
+        ));
+    }
+}
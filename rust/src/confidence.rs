@@ -0,0 +1,157 @@
+use crate::{Anime, MatchCandidate};
+
+/// Weights of the components of a match confidence score
+///
+/// The confidence of a (source, target) pair is the weighted mean of
+/// four components, each scaled between 0 and 1:
+///
+/// - `source_coverage`: the `shared_len` divided by the source length
+/// - `target_coverage`: the `shared_len` divided by the target length
+/// - `offset`: one minus the mean lateral offset divided by the pair's
+///   distance tolerance
+/// - `angle`: one minus the mean angle difference divided by the pair's
+///   angle tolerance
+///
+/// Setting a weight to `0.0` excludes that component. Pairs that share no
+/// portion of their component lines score `0.0` for offset and angle.
+/// Unless [`Anime::record_segments`] is set, offset and angle are unknown
+/// and excluded so the score is the weighted mean of the coverages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfidenceModel {
+    pub source_coverage: f64,
+    pub target_coverage: f64,
+    pub offset: f64,
+    pub angle: f64,
+}
+
+impl Default for ConfidenceModel {
+    fn default() -> Self {
+        Self {
+            source_coverage: 1.0,
+            target_coverage: 1.0,
+            offset: 1.0,
+            angle: 1.0,
+        }
+    }
+}
+
+impl ConfidenceModel {
+    /// Combine the components of a confidence score
+    ///
+    /// Each component is clamped between 0 and 1 before weighting.
+    pub fn score(
+        &self,
+        source_coverage: f64,
+        target_coverage: f64,
+        offset: f64,
+        angle: f64,
+    ) -> f64 {
+        let total = self.source_coverage + self.target_coverage + self.offset + self.angle;
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let clamp = |x: f64| if x.is_nan() { 0.0 } else { x.clamp(0.0, 1.0) };
+        let score = self.source_coverage * clamp(source_coverage)
+            + self.target_coverage * clamp(target_coverage)
+            + self.offset * clamp(offset)
+            + self.angle * clamp(angle);
+        score / total
+    }
+}
+
+impl Anime {
+    /// The confidence of a match with target `target_index`
+    ///
+    /// Scored using `self.confidence_model`. See [`ConfidenceModel`].
+    pub fn confidence(&self, target_index: usize, mc: &MatchCandidate) -> f64 {
+        let i = mc.source_index;
+        let (distance_tolerance, angle_tolerance) = match &self.feature_tolerances {
+            Some(ft) => (
+                ft.distance(i, target_index, self.distance_tolerance),
+                ft.angle(i, target_index, self.angle_tolerance),
+            ),
+            None => (self.distance_tolerance, self.angle_tolerance),
+        };
+
+        let source_coverage = mc.shared_len / self.source_lens[i];
+        let target_coverage = mc.shared_len / self.target_lens[target_index];
+        if !self.record_segments {
+            let model = ConfidenceModel {
+                offset: 0.0,
+                angle: 0.0,
+                ..self.confidence_model
+            };
+            return model.score(source_coverage, target_coverage, 0.0, 0.0);
+        }
+
+        let (offset, angle) = match mc.mean_offset_and_angle() {
+            Some((mean_offset, mean_angle)) => (
+                1.0 - mean_offset / distance_tolerance,
+                1.0 - mean_angle / angle_tolerance,
+            ),
+            None => (0.0, 0.0),
+        };
+
+        self.confidence_model
+            .score(source_coverage, target_coverage, offset, angle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::{coord, LineString};
+
+    #[test]
+    fn test_score_weights() {
+        let model = ConfidenceModel::default();
+        assert_eq!(model.score(1.0, 1.0, 1.0, 1.0), 1.0);
+        assert_eq!(model.score(1.0, 0.0, 1.0, 0.0), 0.5);
+        // components are clamped
+        assert_eq!(model.score(2.0, 2.0, -1.0, -1.0), 0.5);
+
+        let model = ConfidenceModel {
+            source_coverage: 1.0,
+            target_coverage: 0.0,
+            offset: 0.0,
+            angle: 0.0,
+        };
+        assert_eq!(model.score(0.25, 1.0, 1.0, 1.0), 0.25);
+    }
+
+    #[test]
+    fn test_confidence_prefers_closer_match() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.1}, coord! {x: 10.0, y: 0.1}]),
+            LineString::new(vec![coord! {x: 0.0, y: -0.9}, coord! {x: 10.0, y: -0.9}]),
+        ];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+        let matches = anime.matches.get().unwrap();
+
+        let near = anime.confidence(0, &matches[&0][0]);
+        let far = anime.confidence(1, &matches[&1][0]);
+        assert!((near - 0.975).abs() < 1e-12);
+        assert!(near > far);
+    }
+
+    #[test]
+    fn test_confidence_without_segments() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.9},
+            coord! {x: 5.0, y: 0.9},
+        ])];
+        let anime = Anime::new(source.into_iter(), target.into_iter(), 1.0, 5.0);
+        let mc = &anime.matches.get().unwrap()[&0][0];
+
+        // only the source and target coverage are scored
+        assert_eq!(anime.confidence(0, mc), 0.75);
+    }
+}
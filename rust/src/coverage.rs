@@ -0,0 +1,358 @@
+use crate::{Anime, AnimeError, ComponentData};
+use arrow::array::{Float64Array, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use geo::{Euclidean, Length};
+use geo_types::{Coord, Line, LineString};
+use std::sync::Arc;
+
+// parameters closer than this are treated as equal
+const EPS: f64 = 1e-9;
+
+/// An unmatched stretch of a linestring
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    /// The index of the linestring
+    pub feature: usize,
+    pub geometry: LineString,
+}
+
+/// Matched and unmatched length of the source and target
+#[derive(Debug, Clone)]
+pub struct CoverageReport {
+    /// One row per source with columns `feature_id`, `covered_len`,
+    /// `uncovered_len`, `coverage`, and `n_gaps`
+    pub source: RecordBatch,
+    /// As `source`, one row per target
+    pub target: RecordBatch,
+    pub source_gaps: Vec<Gap>,
+    pub target_gaps: Vec<Gap>,
+}
+
+impl Anime {
+    /// Report how much of each linestring is matched and where the gaps are
+    ///
+    /// The covered length of a linestring is the union of the shared portions
+    /// of its component lines in `self.matches`, so portions shared with
+    /// several linestrings are counted once. Uncovered portions of adjacent
+    /// component lines are joined into a single [`Gap`]. Gaps shorter than
+    /// `min_gap` are omitted from the gaps but still count towards the
+    /// uncovered length. Requires [`Anime::record_segments`].
+    ///
+    /// Coverage is measured along the component lines that were indexed,
+    /// which differ from the original geometry when it was simplified with
+    /// [`crate::LoadOptions::simplify_tolerance`]. The covered and uncovered
+    /// lengths are scaled so they sum to the original length in
+    /// `source_lens` or `target_lens`, whereas the gaps are stretches of the
+    /// simplified geometry.
+    pub fn coverage(&self, min_gap: f64) -> Result<CoverageReport, AnimeError> {
+        let inner = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+        self.require_segments()?;
+
+        let mut source = feature_components(
+            self.source_tree.iter().map(|cx| (**cx.geom(), cx.data)),
+            self.source_lens.len(),
+        );
+        let mut target = feature_components(
+            self.target_tree.iter().map(|cy| (cy.geom().0, cy.data)),
+            self.target_lens.len(),
+        );
+
+        for (j, items) in inner.iter() {
+            for mc in items {
+                for s in mc.segments.iter() {
+                    add_interval(&mut source[mc.source_index], s.source_component, &s.source);
+                    add_interval(&mut target[*j], s.target_component, &s.target);
+                }
+            }
+        }
+
+        let (source, source_gaps) = summarise(&source, &self.source_lens, min_gap);
+        let (target, target_gaps) = summarise(&target, &self.target_lens, min_gap);
+        Ok(CoverageReport {
+            source,
+            target,
+            source_gaps,
+            target_gaps,
+        })
+    }
+}
+
+// a component line with its position and the covered parameter intervals
+struct Component {
+    k: usize,
+    line: Line,
+    covered: Vec<(f64, f64)>,
+}
+
+fn feature_components(
+    components: impl Iterator<Item = (Line, ComponentData)>,
+    n: usize,
+) -> Vec<Vec<Component>> {
+    let mut res: Vec<Vec<Component>> = (0..n).map(|_| Vec::new()).collect();
+    for (line, (idx, _, k)) in components {
+        res[idx].push(Component {
+            k,
+            line,
+            covered: Vec::new(),
+        });
+    }
+    res.iter_mut().for_each(|c| c.sort_by_key(|c| c.k));
+    res
+}
+
+fn add_interval(components: &mut [Component], k: usize, portion: &Line) {
+    if let Ok(pos) = components.binary_search_by_key(&k, |c| c.k) {
+        let c = &mut components[pos];
+        let (a, b) = (param(&c.line, portion.start), param(&c.line, portion.end));
+        c.covered.push((a.min(b), a.max(b)));
+    }
+}
+
+// the position of a coordinate along a line between 0 and 1
+pub(crate) fn param(line: &Line, coord: Coord) -> f64 {
+    let d = line.delta();
+    let len_2 = d.x * d.x + d.y * d.y;
+    if len_2 == 0.0 {
+        return 0.0;
+    }
+    let v = coord - line.start;
+    ((v.x * d.x + v.y * d.y) / len_2).clamp(0.0, 1.0)
+}
+
+fn point_at(line: &Line, t: f64) -> Coord {
+    line.start + line.delta() * t
+}
+
+// the parameter intervals of a component line not covered by a match
+fn uncovered(covered: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut covered = covered.to_vec();
+    covered.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut res = Vec::new();
+    let mut t = 0.0;
+    for (a, b) in covered {
+        if a > t + EPS {
+            res.push((t, a));
+        }
+        t = f64::max(t, b);
+    }
+    if t < 1.0 - EPS {
+        res.push((t, 1.0));
+    }
+    res
+}
+
+fn summarise(features: &[Vec<Component>], lens: &[f64], min_gap: f64) -> (RecordBatch, Vec<Gap>) {
+    let mut gaps = Vec::new();
+    let mut rows = Vec::with_capacity(features.len());
+
+    for (feature, components) in features.iter().enumerate() {
+        let n_gaps = gaps.len();
+        let mut total_len = 0.0;
+        let mut uncovered_len = 0.0;
+        // a gap running to the end of the previous component line
+        let mut open: Option<Vec<Coord>> = None;
+        let mut flush = |coords: Vec<Coord>| {
+            let geometry = LineString::new(coords);
+            if geometry.length::<Euclidean>() >= min_gap {
+                gaps.push(Gap { feature, geometry });
+            }
+        };
+
+        for c in components {
+            let len = c.line.length::<Euclidean>();
+            total_len += len;
+            for (a, b) in uncovered(&c.covered) {
+                uncovered_len += (b - a) * len;
+                let (start, end) = (point_at(&c.line, a), point_at(&c.line, b));
+                let coords = match open.take() {
+                    Some(mut coords) if a <= EPS && coords.last() == Some(&start) => {
+                        coords.push(end);
+                        coords
+                    }
+                    other => {
+                        if let Some(coords) = other {
+                            flush(coords);
+                        }
+                        vec![start, end]
+                    }
+                };
+                if b >= 1.0 - EPS {
+                    open = Some(coords);
+                } else {
+                    flush(coords);
+                }
+            }
+            // a covered end breaks the gap
+            if c.covered.iter().any(|(_, b)| *b >= 1.0 - EPS) {
+                if let Some(coords) = open.take() {
+                    flush(coords);
+                }
+            }
+        }
+        if let Some(coords) = open.take() {
+            flush(coords);
+        }
+
+        // scale from the indexed component lines to the original length
+        let (coverage, covered_len, uncovered_len) = if total_len > 0.0 {
+            let coverage = ((total_len - uncovered_len) / total_len).max(0.0);
+            let len = lens[feature];
+            (coverage, coverage * len, (1.0 - coverage) * len)
+        } else {
+            (0.0, 0.0, 0.0)
+        };
+        rows.push((
+            feature,
+            covered_len,
+            uncovered_len,
+            coverage,
+            gaps.len() - n_gaps,
+        ));
+    }
+
+    let schema = Schema::new(vec![
+        Field::new("feature_id", DataType::Int32, false),
+        Field::new("covered_len", DataType::Float64, false),
+        Field::new("uncovered_len", DataType::Float64, false),
+        Field::new("coverage", DataType::Float64, false),
+        Field::new("n_gaps", DataType::Int32, false),
+    ]);
+    let batch = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(Int32Array::from_iter_values(
+                rows.iter().map(|r| r.0 as i32),
+            )),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.1))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.2))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.3))),
+            Arc::new(Int32Array::from_iter_values(
+                rows.iter().map(|r| r.4 as i32),
+            )),
+        ],
+    )
+    .expect("All arrays should be identical lengths");
+    (batch, gaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::AsArray;
+    use arrow::datatypes::Float64Type;
+    use geo_types::coord;
+
+    #[test]
+    fn test_uncovered() {
+        assert_eq!(uncovered(&[]), vec![(0.0, 1.0)]);
+        assert_eq!(
+            uncovered(&[(0.5, 0.7), (0.1, 0.3), (0.2, 0.4)]),
+            vec![(0.0, 0.1), (0.4, 0.5), (0.7, 1.0)]
+        );
+        assert!(uncovered(&[(0.0, 1.0)]).is_empty());
+    }
+
+    #[test]
+    fn test_coverage() {
+        // the source continues past the end of the target
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+            coord! {x: 20.0, y: 0.0},
+            coord! {x: 20.0, y: 10.0},
+        ])];
+        let target = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.5}, coord! {x: 15.0, y: 0.5}]),
+            LineString::new(vec![coord! {x: 0.0, y: 50.0}, coord! {x: 10.0, y: 50.0}]),
+        ];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+        let res = anime.coverage(0.0).unwrap();
+
+        let covered = res.source.column(1).as_primitive::<Float64Type>();
+        let uncovered = res.source.column(2).as_primitive::<Float64Type>();
+        assert!((covered.value(0) - 15.0).abs() < 1e-9);
+        assert!((uncovered.value(0) - 15.0).abs() < 1e-9);
+
+        // the unmatched stretch spans two component lines
+        assert_eq!(res.source_gaps.len(), 1);
+        assert_eq!(
+            res.source_gaps[0].geometry,
+            LineString::new(vec![
+                coord! {x: 15.0, y: 0.0},
+                coord! {x: 20.0, y: 0.0},
+                coord! {x: 20.0, y: 10.0},
+            ])
+        );
+
+        let coverage = res.target.column(3).as_primitive::<Float64Type>();
+        assert!((coverage.value(0) - 1.0).abs() < 1e-9);
+        assert_eq!(coverage.value(1), 0.0);
+        assert_eq!(res.target_gaps.len(), 1);
+        assert_eq!(res.target_gaps[0].feature, 1);
+    }
+
+    #[test]
+    fn test_min_gap() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.5, y: 0.0},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.5},
+            coord! {x: 10.0, y: 0.5},
+        ])];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+        let res = anime.coverage(1.0).unwrap();
+
+        assert!(res.source_gaps.is_empty());
+        let uncovered = res.source.column(2).as_primitive::<Float64Type>();
+        assert!((uncovered.value(0) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_coverage_simplified() {
+        // a jagged source is simplified to a straight line of length 10
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 2.5, y: 0.1},
+            coord! {x: 5.0, y: 0.0},
+            coord! {x: 7.5, y: 0.1},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.5},
+            coord! {x: 5.0, y: 0.5},
+        ])];
+        let options = crate::LoadOptions {
+            simplify_tolerance: Some(0.5),
+            ..Default::default()
+        };
+        let mut anime = Anime::load_geometries_with_options(
+            source.into_iter(),
+            target.into_iter(),
+            1.0,
+            5.0,
+            options,
+        )
+        .unwrap();
+        anime.record_segments = true;
+        anime.find_matches().unwrap();
+        assert_eq!(anime.source_tree.size(), 1);
+        assert!(anime.source_lens[0] > 10.0);
+
+        let res = anime.coverage(0.0).unwrap();
+        let covered = res.source.column(1).as_primitive::<Float64Type>();
+        let uncovered = res.source.column(2).as_primitive::<Float64Type>();
+        let coverage = res.source.column(3).as_primitive::<Float64Type>();
+        assert!((coverage.value(0) - 0.5).abs() < 1e-9);
+        assert!((covered.value(0) + uncovered.value(0) - anime.source_lens[0]).abs() < 1e-9);
+        assert!((covered.value(0) - anime.source_lens[0] / 2.0).abs() < 1e-9);
+
+        // the gap is a stretch of the simplified geometry
+        assert_eq!(
+            res.source_gaps[0].geometry,
+            LineString::new(vec![coord! {x: 5.0, y: 0.0}, coord! {x: 10.0, y: 0.0}])
+        );
+    }
+}
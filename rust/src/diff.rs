@@ -0,0 +1,309 @@
+use crate::{Anime, AnimeError, Side};
+use arrow::array::{Float64Array, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+/// How a feature changed between two versions of a network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+    /// Matched 1:1 with the same extent and position
+    Unchanged,
+    /// Matched 1:1 but extended, shortened or moved
+    Modified,
+    /// One old feature matched to several new features
+    Split,
+    /// Several old features matched to one new feature
+    Merged,
+    /// A new feature without a counterpart in the old network
+    Added,
+    /// An old feature without a counterpart in the new network
+    Removed,
+}
+
+impl Change {
+    /// The name used in the per-feature table
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Change::Unchanged => "unchanged",
+            Change::Modified => "modified",
+            Change::Split => "split",
+            Change::Merged => "merged",
+            Change::Added => "added",
+            Change::Removed => "removed",
+        }
+    }
+}
+
+/// Thresholds used to classify changes
+///
+/// See [`Anime::diff`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffOptions {
+    /// A match is ignored unless its shared length is at least this share
+    /// of the shorter feature. Removes incidental matches at junctions.
+    pub min_share: f64,
+    /// Features with less coverage than this are added or removed
+    pub min_coverage: f64,
+    /// 1:1 matches where both features have at least this coverage and
+    /// a mean offset of at most `max_offset` are unchanged
+    pub unchanged_coverage: f64,
+    /// See `unchanged_coverage`
+    pub max_offset: f64,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            min_share: 0.1,
+            min_coverage: 0.5,
+            unchanged_coverage: 0.95,
+            max_offset: 0.0,
+        }
+    }
+}
+
+/// The number of features in each change class
+///
+/// Unchanged, modified, split and removed features are counted in the old
+/// network. Merged and added features are counted in the new network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffSummary {
+    pub n_unchanged: usize,
+    pub n_modified: usize,
+    pub n_split: usize,
+    pub n_merged: usize,
+    pub n_added: usize,
+    pub n_removed: usize,
+}
+
+/// The changes between two versions of a network
+#[derive(Debug, Clone)]
+pub struct Diff {
+    /// The change of each old (source) feature
+    pub source: Vec<Change>,
+    /// The change of each new (target) feature
+    pub target: Vec<Change>,
+    pub summary: DiffSummary,
+    /// One row per feature with columns `side`, `feature_id`, `change`,
+    /// `coverage`, and `n_matches`
+    pub table: RecordBatch,
+}
+
+impl Anime {
+    /// Classify the changes between the source (old) and target (new) networks
+    ///
+    /// Matches sharing less than `min_share` of the shorter feature are
+    /// discarded. The coverage of a feature is the shared length of its
+    /// remaining matches divided by its length, capped at 1. Features with
+    /// less than `min_coverage` are removed (source) or added (target).
+    /// Otherwise the m:n structure of the remaining matches decides:
+    ///
+    /// - an old feature matched to several new features is split, as is
+    ///   each of those new features
+    /// - a new feature matched to several old features is merged, as is
+    ///   each of those old features
+    /// - a 1:1 match is unchanged or modified depending on the coverage of
+    ///   both features and their mean offset, so [`Anime::record_segments`]
+    ///   is required
+    pub fn diff(&self, options: &DiffOptions) -> Result<Diff, AnimeError> {
+        let inner = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+        self.require_segments()?;
+        let n_source = self.source_lens.len();
+        let n_target = self.target_lens.len();
+
+        let mut source_edges = vec![Vec::new(); n_source];
+        let mut target_edges = vec![Vec::new(); n_target];
+        let mut source_cov = vec![0.0; n_source];
+        let mut target_cov = vec![0.0; n_target];
+        let mut unchanged = BTreeSet::new();
+
+        for (j, items) in inner.iter() {
+            let target_len = self.target_lens[*j];
+            for mc in items {
+                let i = mc.source_index;
+                let source_len = self.source_lens[i];
+                if mc.shared_len < options.min_share * source_len.min(target_len) {
+                    continue;
+                }
+                source_edges[i].push(*j);
+                target_edges[*j].push(i);
+                source_cov[i] += mc.shared_len / source_len;
+                target_cov[*j] += mc.shared_len / target_len;
+
+                let near = mc
+                    .mean_offset_and_angle()
+                    .is_some_and(|(offset, _)| offset <= options.max_offset);
+                if near {
+                    unchanged.insert((i, *j));
+                }
+            }
+        }
+        source_cov.iter_mut().for_each(|c| *c = f64::min(*c, 1.0));
+        target_cov.iter_mut().for_each(|c| *c = f64::min(*c, 1.0));
+
+        let is_unchanged = |i: usize, j: usize| {
+            source_cov[i] >= options.unchanged_coverage
+                && target_cov[j] >= options.unchanged_coverage
+                && unchanged.contains(&(i, j))
+        };
+        let one_to_one = |i: usize, j: usize| {
+            if is_unchanged(i, j) {
+                Change::Unchanged
+            } else {
+                Change::Modified
+            }
+        };
+
+        let source = (0..n_source)
+            .map(|i| match source_edges[i].as_slice() {
+                [] => Change::Removed,
+                _ if source_cov[i] < options.min_coverage => Change::Removed,
+                [j] if target_edges[*j].len() > 1 => Change::Merged,
+                [j] => one_to_one(i, *j),
+                _ => Change::Split,
+            })
+            .collect::<Vec<_>>();
+        let target = (0..n_target)
+            .map(|j| match target_edges[j].as_slice() {
+                [] => Change::Added,
+                _ if target_cov[j] < options.min_coverage => Change::Added,
+                [i] if source_edges[*i].len() > 1 => Change::Split,
+                [i] => one_to_one(*i, j),
+                _ => Change::Merged,
+            })
+            .collect::<Vec<_>>();
+
+        let count =
+            |changes: &[Change], change: Change| changes.iter().filter(|c| **c == change).count();
+        let summary = DiffSummary {
+            n_unchanged: count(&source, Change::Unchanged),
+            n_modified: count(&source, Change::Modified),
+            n_split: count(&source, Change::Split),
+            n_merged: count(&target, Change::Merged),
+            n_added: count(&target, Change::Added),
+            n_removed: count(&source, Change::Removed),
+        };
+
+        let rows = source
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (Side::Source, i, *c, source_cov[i], source_edges[i].len()))
+            .chain(
+                target
+                    .iter()
+                    .enumerate()
+                    .map(|(j, c)| (Side::Target, j, *c, target_cov[j], target_edges[j].len())),
+            )
+            .collect::<Vec<_>>();
+
+        let schema = Schema::new(vec![
+            Field::new("side", DataType::Utf8, false),
+            Field::new("feature_id", DataType::Int32, false),
+            Field::new("change", DataType::Utf8, false),
+            Field::new("coverage", DataType::Float64, false),
+            Field::new("n_matches", DataType::Int32, false),
+        ]);
+        let table = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|r| r.0.to_string()),
+                )),
+                Arc::new(Int32Array::from_iter_values(
+                    rows.iter().map(|r| r.1 as i32),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|r| r.2.as_str()),
+                )),
+                Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.3))),
+                Arc::new(Int32Array::from_iter_values(
+                    rows.iter().map(|r| r.4 as i32),
+                )),
+            ],
+        )
+        .expect("All arrays should be identical lengths");
+
+        Ok(Diff {
+            source,
+            target,
+            summary,
+            table,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::AsArray;
+    use geo_types::{coord, LineString};
+
+    fn line(x0: f64, x1: f64, y: f64) -> LineString {
+        LineString::new(vec![coord! {x: x0, y: y}, coord! {x: x1, y: y}])
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = vec![
+            line(0.0, 10.0, 0.0),
+            line(0.0, 10.0, 20.0),
+            line(0.0, 20.0, 40.0),
+            line(0.0, 10.0, 60.0),
+            line(10.0, 20.0, 60.0),
+            line(0.0, 10.0, 80.0),
+        ];
+        let new = vec![
+            line(0.0, 10.0, 0.0),
+            // moved
+            line(0.0, 10.0, 20.5),
+            line(0.0, 10.0, 40.0),
+            line(10.0, 20.0, 40.0),
+            line(0.0, 20.0, 60.0),
+            line(0.0, 10.0, 100.0),
+        ];
+        let anime = Anime::new_with_segments(old.into_iter(), new.into_iter(), 1.0, 5.0);
+        let options = DiffOptions {
+            max_offset: 0.1,
+            ..Default::default()
+        };
+        let res = anime.diff(&options).unwrap();
+
+        use Change::*;
+        assert_eq!(
+            res.source,
+            vec![Unchanged, Modified, Split, Merged, Merged, Removed]
+        );
+        assert_eq!(
+            res.target,
+            vec![Unchanged, Modified, Split, Split, Merged, Added]
+        );
+        assert_eq!(
+            res.summary,
+            DiffSummary {
+                n_unchanged: 1,
+                n_modified: 1,
+                n_split: 1,
+                n_merged: 1,
+                n_added: 1,
+                n_removed: 1,
+            }
+        );
+
+        assert_eq!(res.table.num_rows(), 12);
+        let change = res.table.column(2).as_string::<i32>();
+        assert_eq!(change.value(2), "split");
+        assert_eq!(change.value(11), "added");
+    }
+
+    #[test]
+    fn test_diff_requires_matches() {
+        let anime = Anime::load_geometries(
+            vec![line(0.0, 10.0, 0.0)].into_iter(),
+            vec![line(0.0, 10.0, 0.0)].into_iter(),
+            1.0,
+            5.0,
+        );
+        assert!(anime.diff(&DiffOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use geo_types::{Coord, Line};
+
+/// Smooth the direction of component lines used for angle comparisons
+///
+/// By default the angle of each component line is taken from its own slope.
+/// Short, jittery segments, common in GPS traces and hand-digitised data,
+/// can fail the angle test even when the feature as a whole is parallel.
+///
+/// With smoothing, the direction of a component line is the sum of the
+/// vectors of the lines within `window` positions on either side of it in
+/// the same feature. Summing the vectors weights each line by its length.
+/// Lines shorter than `min_len` do not contribute. If no line in the window
+/// is long enough, or their vectors cancel out, the whole feature is used.
+/// If that also fails the line's own direction is used.
+///
+/// Shared lengths are always calculated from the lines' own slopes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionSmoothing {
+    /// The number of neighbouring lines on each side included in the window
+    pub window: usize,
+    /// Lines shorter than this are ignored when calculating directions
+    pub min_len: f64,
+}
+
+/// Slopes of a feature's component lines used for angle comparisons
+pub(crate) fn component_slopes(lines: &[Line], smoothing: Option<&DirectionSmoothing>) -> Vec<f64> {
+    let Some(smoothing) = smoothing else {
+        return lines.iter().map(|li| li.slope()).collect();
+    };
+
+    let is_long = |li: &Line| {
+        let d = li.delta();
+        d.x.hypot(d.y) >= smoothing.min_len
+    };
+    // deltas that cancel out, such as an out-and-back, have no direction
+    let sum_deltas = |lines: &[Line]| {
+        lines
+            .iter()
+            .filter(|li| is_long(li))
+            .fold(None, |acc: Option<Coord>, li| {
+                Some(acc.map_or(li.delta(), |a| a + li.delta()))
+            })
+            .filter(|d| d.x != 0.0 || d.y != 0.0)
+    };
+
+    let feature_delta = sum_deltas(lines);
+
+    (0..lines.len())
+        .map(|k| {
+            let start = k.saturating_sub(smoothing.window);
+            let end = (k + smoothing.window + 1).min(lines.len());
+            let delta = sum_deltas(&lines[start..end])
+                .or(feature_delta)
+                .unwrap_or(lines[k].delta());
+            delta.y / delta.x
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::{coord, LineString};
+
+    fn zig_zag() -> Vec<Line> {
+        LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 1.0, y: 1.0},
+            coord! {x: 2.0, y: 0.0},
+            coord! {x: 3.0, y: 1.0},
+            coord! {x: 4.0, y: 0.0},
+        ])
+        .lines()
+        .collect()
+    }
+
+    #[test]
+    fn test_component_slopes_unsmoothed() {
+        let slopes = component_slopes(&zig_zag(), None);
+        assert_eq!(slopes, vec![1.0, -1.0, 1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_component_slopes_window() {
+        let smoothing = DirectionSmoothing {
+            window: 1,
+            min_len: 0.0,
+        };
+        let slopes = component_slopes(&zig_zag(), Some(&smoothing));
+
+        // the end lines average two neighbours, inner lines average three
+        assert_eq!(slopes[0], 0.0);
+        assert_eq!(slopes[1], 1.0 / 3.0);
+        assert_eq!(slopes[2], -1.0 / 3.0);
+        assert_eq!(slopes[3], 0.0);
+    }
+
+    #[test]
+    fn test_component_slopes_min_len() {
+        let lines = LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+            coord! {x: 10.1, y: 0.5},
+            coord! {x: 20.0, y: 0.5},
+        ])
+        .lines()
+        .collect::<Vec<_>>();
+
+        let smoothing = DirectionSmoothing {
+            window: 0,
+            min_len: 1.0,
+        };
+        let slopes = component_slopes(&lines, Some(&smoothing));
+
+        // the short middle line takes the direction of the whole feature
+        assert_eq!(slopes[0], 0.0);
+        assert_eq!(slopes[1], 0.0);
+        assert_eq!(slopes[2], 0.0);
+    }
+
+    #[test]
+    fn test_component_slopes_all_short() {
+        let lines = vec![Line::new(coord! {x: 0.0, y: 0.0}, coord! {x: 0.1, y: 0.1})];
+        let smoothing = DirectionSmoothing {
+            window: 3,
+            min_len: 1.0,
+        };
+        assert_eq!(component_slopes(&lines, Some(&smoothing)), vec![1.0]);
+    }
+
+    #[test]
+    fn test_component_slopes_reversing() {
+        // an out-and-back where every window and the feature sum to zero
+        let lines = LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+            coord! {x: 0.0, y: 0.0},
+        ])
+        .lines()
+        .collect::<Vec<_>>();
+        let smoothing = DirectionSmoothing {
+            window: 1,
+            min_len: 0.0,
+        };
+        let slopes = component_slopes(&lines, Some(&smoothing));
+        assert!(slopes.iter().all(|s| !s.is_nan()));
+        assert_eq!(slopes, vec![0.0, 0.0]);
+    }
+}
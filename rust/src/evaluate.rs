@@ -0,0 +1,281 @@
+use crate::get_matches::read_match_ids;
+use crate::{Anime, AnimeError};
+use arrow::array::{Array, AsArray, Float64Array, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Float64Type, Schema};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+/// Agreement between predicted matches and a reference match table
+///
+/// Pair-level metrics count (source, target) pairs. Length-weighted
+/// metrics weight each pair by its `shared_len` so that pairs sharing more
+/// length count for more. Predicted pairs use their matched `shared_len`.
+/// False negatives were never matched so they use the `shared_len` of the
+/// reference table when it has that column and otherwise the length of
+/// the shorter of the two geometries.
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+    pub n_true_positive: usize,
+    pub n_false_positive: usize,
+    pub n_false_negative: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    pub weighted_precision: f64,
+    pub weighted_recall: f64,
+    pub weighted_f1: f64,
+    /// Predicted pairs missing from the reference with columns
+    /// `source_id`, `target_id`, and `shared_len`
+    pub false_positives: RecordBatch,
+    /// Reference pairs that were not predicted with columns
+    /// `source_id`, `target_id`, and `shared_len`
+    pub false_negatives: RecordBatch,
+}
+
+/// Compare the matches of `predicted` against a reference match table
+///
+/// `truth` must contain integer `source_id` and `target_id` columns
+/// referring to the geometries of `predicted`, and may contain a float
+/// `shared_len` column. Duplicate pairs are counted once. Predicted pairs
+/// with no shared length are ignored.
+pub fn evaluate(predicted: &Anime, truth: &RecordBatch) -> Result<Evaluation, AnimeError> {
+    let inner = predicted.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+    let truth_lens = read_truth_lens(truth)?;
+    let truth = truth_lens.keys().copied().collect::<BTreeSet<_>>();
+
+    let n_source = predicted.source_lens.len();
+    let n_target = predicted.target_lens.len();
+    if truth.iter().any(|(i, j)| *i >= n_source || *j >= n_target) {
+        return Err(AnimeError::InvalidMatchTable(
+            "ids must refer to the `source` and `target` lines".to_string(),
+        ));
+    }
+
+    let pred_lens = inner
+        .iter()
+        .flat_map(|(j, items)| {
+            items
+                .iter()
+                .filter(|mc| mc.shared_len > 0.0)
+                .map(move |mc| ((mc.source_index, *j), mc.shared_len))
+        })
+        .collect::<BTreeMap<_, _>>();
+    let pred = pred_lens.keys().copied().collect::<BTreeSet<_>>();
+
+    let pair_len = |pair: &(usize, usize)| match (pred_lens.get(pair), truth_lens.get(pair)) {
+        (Some(len), _) => *len,
+        (None, Some(Some(len))) => *len,
+        _ => predicted.source_lens[pair.0].min(predicted.target_lens[pair.1]),
+    };
+    let total = |pairs: &[&(usize, usize)]| pairs.iter().map(|p| pair_len(p)).sum::<f64>();
+
+    let tp = pred.intersection(&truth).collect::<Vec<_>>();
+    let fp = pred.difference(&truth).collect::<Vec<_>>();
+    let fn_ = truth.difference(&pred).collect::<Vec<_>>();
+
+    let (precision, recall, f1) = scores(tp.len() as f64, fp.len() as f64, fn_.len() as f64);
+    let (weighted_precision, weighted_recall, weighted_f1) =
+        scores(total(&tp), total(&fp), total(&fn_));
+
+    Ok(Evaluation {
+        n_true_positive: tp.len(),
+        n_false_positive: fp.len(),
+        n_false_negative: fn_.len(),
+        precision,
+        recall,
+        f1,
+        weighted_precision,
+        weighted_recall,
+        weighted_f1,
+        false_positives: pair_table(&fp, pair_len),
+        false_negatives: pair_table(&fn_, pair_len),
+    })
+}
+
+// the shared length of each reference pair, if known
+fn read_truth_lens(
+    truth: &RecordBatch,
+) -> Result<BTreeMap<(usize, usize), Option<f64>>, AnimeError> {
+    let ids = read_match_ids(truth)?;
+    let lens = match truth.column_by_name("shared_len") {
+        Some(col) => {
+            let col = arrow::compute::cast(col, &DataType::Float64).map_err(|_| {
+                AnimeError::InvalidMatchTable("`shared_len` must contain numbers".to_string())
+            })?;
+            let col = col.as_primitive::<Float64Type>();
+            (0..col.len())
+                .map(|k| col.is_valid(k).then(|| col.value(k)))
+                .collect::<Vec<_>>()
+        }
+        None => vec![None; ids.len()],
+    };
+
+    let mut res = BTreeMap::new();
+    for (pair, len) in ids.into_iter().zip(lens) {
+        // keep the first known length of a duplicated pair
+        let entry = res.entry(pair).or_insert(None);
+        if entry.is_none() {
+            *entry = len;
+        }
+    }
+    Ok(res)
+}
+
+// precision, recall, and F1 from true positive, false positive, and false
+// negative totals. Undefined ratios are 0.
+fn scores(tp: f64, fp: f64, fn_: f64) -> (f64, f64, f64) {
+    let ratio = |a: f64, b: f64| if b > 0.0 { a / b } else { 0.0 };
+    let precision = ratio(tp, tp + fp);
+    let recall = ratio(tp, tp + fn_);
+    let f1 = ratio(2.0 * precision * recall, precision + recall);
+    (precision, recall, f1)
+}
+
+fn pair_table(pairs: &[&(usize, usize)], pair_len: impl Fn(&(usize, usize)) -> f64) -> RecordBatch {
+    let schema = Schema::new(vec![
+        Field::new("source_id", DataType::Int32, false),
+        Field::new("target_id", DataType::Int32, false),
+        Field::new("shared_len", DataType::Float64, false),
+    ]);
+
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(Int32Array::from_iter_values(
+                pairs.iter().map(|(i, _)| *i as i32),
+            )),
+            Arc::new(Int32Array::from_iter_values(
+                pairs.iter().map(|(_, j)| *j as i32),
+            )),
+            Arc::new(Float64Array::from_iter_values(
+                pairs.iter().map(|p| pair_len(p)),
+            )),
+        ],
+    )
+    .expect("All arrays should be identical lengths")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MatchCandidate;
+    use arrow::datatypes::Int32Type;
+    use geo_types::{coord, LineString};
+
+    fn truth_table(pairs: &[(i32, i32)]) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("source_id", DataType::Int32, false),
+            Field::new("target_id", DataType::Int32, false),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from_iter_values(pairs.iter().map(|p| p.0))),
+                Arc::new(Int32Array::from_iter_values(pairs.iter().map(|p| p.1))),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn create_anime() -> Anime {
+        // source 0 matches target 0, source 1 (short) matches target 1
+        let source = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 30.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 50.0}, coord! {x: 10.0, y: 50.0}]),
+        ];
+        let target = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.5}, coord! {x: 30.0, y: 0.5}]),
+            LineString::new(vec![coord! {x: 0.0, y: 50.5}, coord! {x: 10.0, y: 50.5}]),
+            LineString::new(vec![coord! {x: 0.0, y: 90.0}, coord! {x: 10.0, y: 90.0}]),
+        ];
+        Anime::new(source.into_iter(), target.into_iter(), 1.0, 5.0)
+    }
+
+    #[test]
+    fn test_scores() {
+        assert_eq!(scores(1.0, 1.0, 0.0), (0.5, 1.0, 2.0 / 3.0));
+        assert_eq!(scores(0.0, 0.0, 0.0), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_evaluate_perfect() {
+        let anime = create_anime();
+        let res = evaluate(&anime, &truth_table(&[(0, 0), (1, 1)])).unwrap();
+        assert_eq!(res.n_true_positive, 2);
+        assert_eq!(res.f1, 1.0);
+        assert_eq!(res.weighted_f1, 1.0);
+        assert_eq!(res.false_positives.num_rows(), 0);
+    }
+
+    #[test]
+    fn test_evaluate_errors() {
+        let anime = create_anime();
+        // source 1 is labelled with the unmatched target 2
+        let res = evaluate(&anime, &truth_table(&[(0, 0), (1, 2)])).unwrap();
+
+        assert_eq!(res.n_true_positive, 1);
+        assert_eq!(res.n_false_positive, 1);
+        assert_eq!(res.n_false_negative, 1);
+        assert_eq!(res.precision, 0.5);
+        assert_eq!(res.recall, 0.5);
+        // the long pair is correct so weighted precision is 30 / 40
+        assert_eq!(res.weighted_precision, 0.75);
+        // the false negative is weighted by the shorter geometry
+        assert_eq!(res.weighted_recall, 0.75);
+
+        let fp = &res.false_positives;
+        assert_eq!(fp.column(0).as_primitive::<Int32Type>().value(0), 1);
+        assert_eq!(fp.column(1).as_primitive::<Int32Type>().value(0), 1);
+        let fn_ = &res.false_negatives;
+        assert_eq!(fn_.column(1).as_primitive::<Int32Type>().value(0), 2);
+    }
+
+    #[test]
+    fn test_evaluate_out_of_range() {
+        let anime = create_anime();
+        let res = evaluate(&anime, &truth_table(&[(5, 0)]));
+        assert!(matches!(res, Err(AnimeError::InvalidMatchTable(_))));
+    }
+
+    #[test]
+    fn test_evaluate_truth_shared_len() {
+        let anime = create_anime();
+        let schema = Schema::new(vec![
+            Field::new("source_id", DataType::Int32, false),
+            Field::new("target_id", DataType::Int32, false),
+            Field::new("shared_len", DataType::Float64, true),
+        ]);
+        let truth = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(vec![0, 1])),
+                Arc::new(Int32Array::from(vec![0, 2])),
+                Arc::new(Float64Array::from(vec![None, Some(5.0)])),
+            ],
+        )
+        .unwrap();
+        let res = evaluate(&anime, &truth).unwrap();
+
+        // the false negative is weighted by its reference shared length
+        assert_eq!(res.weighted_recall, 30.0 / 35.0);
+        let fn_ = res.false_negatives.column(2).as_primitive::<Float64Type>();
+        assert_eq!(fn_.value(0), 5.0);
+    }
+
+    #[test]
+    fn test_evaluate_ignores_zero_shared_len() {
+        let mut anime = create_anime();
+        let mut matches = anime.matches.take().unwrap();
+        matches.entry(2).or_default().push(MatchCandidate {
+            source_index: 0,
+            shared_len: 0.0,
+            aligned_len: 0.0,
+            segments: vec![],
+        });
+        anime.matches = matches.into();
+
+        let res = evaluate(&anime, &truth_table(&[(0, 0), (1, 1)])).unwrap();
+        assert_eq!(res.n_false_positive, 0);
+        assert_eq!(res.precision, 1.0);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::assign::find;
+use crate::{Anime, AnimeError, MatchCandidate, MatchesMap};
+use std::collections::{BTreeMap, VecDeque};
+
+// flows and capacities smaller than this are treated as zero
+const EPS: f64 = 1e-9;
+
+impl Anime {
+    /// Allocate shared length optimally using min-cost flow
+    ///
+    /// The source and target lengths are treated as capacities and each
+    /// matched pair as an edge whose capacity is its `shared_len`. The cost
+    /// of allocating length along an edge is the pair's mean lateral offset
+    /// divided by its distance tolerance plus its mean angle difference
+    /// divided by its angle tolerance. Pairs that share no portion of their
+    /// component lines have the largest cost of `2.0`.
+    ///
+    /// The maximum amount of length is allocated at minimum total cost so
+    /// no source or target is allocated more than its length. The returned
+    /// matches contain only pairs that were allocated length, with
+    /// `shared_len` set to the allocated length and `aligned_len` scaled
+    /// proportionally. Requires [`Anime::record_segments`].
+    ///
+    /// Each connected component of matched sources and targets is solved
+    /// as its own flow problem.
+    pub fn optimal_matches(&self) -> Result<MatchesMap, AnimeError> {
+        let inner = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+        self.require_segments()?;
+
+        // matched sources and targets that share no path are independent
+        let n_source = self.source_lens.len();
+        let mut parent = (0..n_source + self.target_lens.len()).collect::<Vec<_>>();
+        for (j, items) in inner.iter() {
+            for mc in items.iter() {
+                let a = find(&mut parent, mc.source_index);
+                let b = find(&mut parent, n_source + j);
+                parent[a] = b;
+            }
+        }
+
+        let mut components: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+        for (j, items) in inner.iter() {
+            let root = find(&mut parent, n_source + j);
+            components
+                .entry(root)
+                .or_default()
+                .extend((0..items.len()).map(|k| (*j, k)));
+        }
+
+        let pairs = components
+            .into_values()
+            .flat_map(|component| self.allocate_component(inner, component));
+
+        let mut res = MatchesMap::new();
+        for (j, k, flow) in pairs {
+            if flow <= EPS {
+                continue;
+            }
+            let mc = &inner[&j][k];
+            res.entry(j).or_default().push(MatchCandidate {
+                source_index: mc.source_index,
+                shared_len: flow,
+                aligned_len: mc.aligned_len * flow / mc.shared_len,
+                segments: mc.segments.clone(),
+            });
+        }
+        Ok(res)
+    }
+
+    /// A copy of `self` whose matches are the optimal allocation
+    ///
+    /// Interpolating with the result weights each pair by its allocated
+    /// length instead of its raw shared length. See [`Anime::optimal_matches`].
+    pub fn optimal(&self) -> Result<Anime, AnimeError> {
+        let matches = self.optimal_matches()?;
+        let mut res = self.clone();
+        res.matches = matches.into();
+        Ok(res)
+    }
+
+    // the flow along each `(target, position)` pair of a connected component
+    fn allocate_component(
+        &self,
+        inner: &MatchesMap,
+        pairs: Vec<(usize, usize)>,
+    ) -> Vec<(usize, usize, f64)> {
+        let mut sources = pairs
+            .iter()
+            .map(|(j, k)| inner[j][*k].source_index)
+            .collect::<Vec<_>>();
+        let mut targets = pairs.iter().map(|(j, _)| *j).collect::<Vec<_>>();
+        sources.sort_unstable();
+        sources.dedup();
+        targets.sort_unstable();
+        targets.dedup();
+
+        let n_source = sources.len();
+        let s = n_source + targets.len();
+        let t = s + 1;
+        let mut graph = FlowGraph::new(t + 1);
+
+        for (si, i) in sources.iter().enumerate() {
+            graph.add_edge(s, si, self.source_lens[*i], 0.0);
+        }
+        for (tj, j) in targets.iter().enumerate() {
+            graph.add_edge(n_source + tj, t, self.target_lens[*j], 0.0);
+        }
+
+        let edges = pairs
+            .iter()
+            .map(|(j, k)| {
+                let mc = &inner[j][*k];
+                let si = sources.binary_search(&mc.source_index).unwrap();
+                let tj = targets.binary_search(j).unwrap();
+                let cost = self.allocation_cost(*j, mc);
+                graph.add_edge(si, n_source + tj, mc.shared_len, cost)
+            })
+            .collect::<Vec<_>>();
+
+        graph.solve(s, t);
+
+        pairs
+            .into_iter()
+            .zip(edges)
+            .map(|((j, k), edge)| (j, k, graph.flow(edge)))
+            .collect()
+    }
+
+    fn allocation_cost(&self, target_index: usize, mc: &MatchCandidate) -> f64 {
+        let i = mc.source_index;
+        let (distance_tolerance, angle_tolerance) = match &self.feature_tolerances {
+            Some(ft) => (
+                ft.distance(i, target_index, self.distance_tolerance),
+                ft.angle(i, target_index, self.angle_tolerance),
+            ),
+            None => (self.distance_tolerance, self.angle_tolerance),
+        };
+
+        match mc.mean_offset_and_angle() {
+            Some((offset, angle)) => {
+                (offset / distance_tolerance).clamp(0.0, 1.0)
+                    + (angle / angle_tolerance).clamp(0.0, 1.0)
+            }
+            None => 2.0,
+        }
+    }
+}
+
+struct FlowEdge {
+    to: usize,
+    cap: f64,
+    cost: f64,
+}
+
+/// A residual graph solved with successive shortest paths
+///
+/// Each edge is stored next to its reverse edge so the reverse of
+/// edge `e` is `e ^ 1`.
+struct FlowGraph {
+    edges: Vec<FlowEdge>,
+    original_cap: Vec<f64>,
+    adj: Vec<Vec<usize>>,
+}
+
+impl FlowGraph {
+    fn new(n: usize) -> Self {
+        Self {
+            edges: Vec::new(),
+            original_cap: Vec::new(),
+            adj: vec![Vec::new(); n],
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, cap: f64, cost: f64) -> usize {
+        let e = self.edges.len();
+        self.edges.push(FlowEdge { to, cap, cost });
+        self.edges.push(FlowEdge {
+            to: from,
+            cap: 0.0,
+            cost: -cost,
+        });
+        self.original_cap.extend([cap, 0.0]);
+        self.adj[from].push(e);
+        self.adj[to].push(e + 1);
+        e
+    }
+
+    fn flow(&self, e: usize) -> f64 {
+        self.original_cap[e] - self.edges[e].cap
+    }
+
+    // minimum cost maximum flow from s to t
+    fn solve(&mut self, s: usize, t: usize) {
+        let n = self.adj.len();
+        loop {
+            // shortest path by cost through the residual graph (SPFA)
+            let mut dist = vec![f64::INFINITY; n];
+            let mut prev_edge = vec![usize::MAX; n];
+            let mut in_queue = vec![false; n];
+            let mut queue = VecDeque::from([s]);
+            dist[s] = 0.0;
+
+            while let Some(u) = queue.pop_front() {
+                in_queue[u] = false;
+                for &e in self.adj[u].iter() {
+                    let edge = &self.edges[e];
+                    let d = dist[u] + edge.cost;
+                    if edge.cap > EPS && d < dist[edge.to] - EPS {
+                        dist[edge.to] = d;
+                        prev_edge[edge.to] = e;
+                        if !in_queue[edge.to] {
+                            in_queue[edge.to] = true;
+                            queue.push_back(edge.to);
+                        }
+                    }
+                }
+            }
+
+            if dist[t].is_infinite() {
+                break;
+            }
+
+            // bottleneck capacity along the path
+            let mut push = f64::INFINITY;
+            let mut v = t;
+            while v != s {
+                let e = prev_edge[v];
+                push = push.min(self.edges[e].cap);
+                v = self.edges[e ^ 1].to;
+            }
+
+            let mut v = t;
+            while v != s {
+                let e = prev_edge[v];
+                self.edges[e].cap -= push;
+                self.edges[e ^ 1].cap += push;
+                v = self.edges[e ^ 1].to;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Float64Array;
+    use geo_types::{coord, LineString};
+
+    #[test]
+    fn test_flow_graph() {
+        // two paths from 0 to 3, the cheaper one has less capacity
+        let mut graph = FlowGraph::new(4);
+        let a = graph.add_edge(0, 1, 2.0, 1.0);
+        let b = graph.add_edge(0, 2, 5.0, 3.0);
+        graph.add_edge(1, 3, 5.0, 0.0);
+        graph.add_edge(2, 3, 2.0, 0.0);
+        graph.solve(0, 3);
+
+        assert_eq!(graph.flow(a), 2.0);
+        assert_eq!(graph.flow(b), 2.0);
+    }
+
+    #[test]
+    fn test_optimal_matches_respects_target_length() {
+        // both sources overlap the target entirely
+        let source = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.1}, coord! {x: 10.0, y: 0.1}]),
+            LineString::new(vec![coord! {x: 0.0, y: -0.8}, coord! {x: 10.0, y: -0.8}]),
+        ];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+
+        let raw_total: f64 = anime.matches.get().unwrap()[&0]
+            .iter()
+            .map(|mc| mc.shared_len)
+            .sum();
+        assert!((raw_total - 20.0).abs() < 1e-9);
+
+        let optimal = anime.optimal_matches().unwrap();
+        let allocated = &optimal[&0];
+        // the target is allocated once, entirely to the nearer source
+        assert_eq!(allocated.len(), 1);
+        assert_eq!(allocated[0].source_index, 0);
+        assert!((allocated[0].shared_len - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_optimal_splits_source() {
+        // a single source is covered by two consecutive targets
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.1}, coord! {x: 5.0, y: 0.1}]),
+            LineString::new(vec![coord! {x: 5.0, y: 0.1}, coord! {x: 10.0, y: 0.1}]),
+        ];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+
+        let optimal = anime.optimal().unwrap();
+        let res = optimal
+            .interpolate_extensive(&Float64Array::from(vec![100.0]))
+            .unwrap();
+        assert!((res.value(0) - 50.0).abs() < 1e-9);
+        assert!((res.value(1) - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_optimal_matches_components() {
+        // the same overlap twice, far enough apart to be separate components
+        let source = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.1}, coord! {x: 10.0, y: 0.1}]),
+            LineString::new(vec![coord! {x: 0.0, y: -0.8}, coord! {x: 10.0, y: -0.8}]),
+            LineString::new(vec![coord! {x: 0.0, y: 100.1}, coord! {x: 10.0, y: 100.1}]),
+            LineString::new(vec![coord! {x: 0.0, y: 99.2}, coord! {x: 10.0, y: 99.2}]),
+        ];
+        let target = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 100.0}, coord! {x: 10.0, y: 100.0}]),
+        ];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+
+        let optimal = anime.optimal_matches().unwrap();
+        for (j, i) in [(0, 0), (1, 2)] {
+            let allocated = &optimal[&j];
+            assert_eq!(allocated.len(), 1);
+            assert_eq!(allocated[0].source_index, i);
+            assert!((allocated[0].shared_len - 10.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_optimal_requires_matches() {
+        let anime = Anime::load_geometries(std::iter::empty(), std::iter::empty(), 1.0, 5.0);
+        assert!(matches!(
+            anime.optimal_matches(),
+            Err(AnimeError::MatchesNotFound)
+        ));
+    }
+}
@@ -1,8 +1,16 @@
 use crate::{Anime, AnimeError, MatchCandidate};
-use arrow::array::{Float64Array, Int32Array, RecordBatch};
+use arrow::array::{Array, AsArray, BooleanArray, Float64Array, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Int64Type};
 use std::sync::Arc;
 
 impl Anime {
+    /// Extract the matches as a `RecordBatch`
+    ///
+    /// Each row is a (source, target) pair with its `shared_len`, the
+    /// `shared_len` weighted by the length of the source and target, and
+    /// whether the pair is `aligned`. A pair is aligned when at least half
+    /// of its shared length is digitised in the same direction. The
+    /// `confidence` is scored with `self.confidence_model`.
     pub fn get_matches(&self) -> Result<RecordBatch, AnimeError> {
         // create the schema
         let schema = arrow::datatypes::Schema::new(vec![
@@ -19,17 +27,16 @@ impl Anime {
                 arrow::datatypes::DataType::Float64,
                 false,
             ),
+            arrow::datatypes::Field::new("aligned", arrow::datatypes::DataType::Boolean, false),
+            arrow::datatypes::Field::new("confidence", arrow::datatypes::DataType::Float64, false),
         ]);
 
         let schema = Arc::new(schema);
 
-        let inner = self
-            .matches
-            .get()
-            .ok_or_else(|| AnimeError::MatchesNotFound)?;
+        let inner = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
 
         // count the resultant vector sizes
-        let n: usize = inner.iter().map(|(_, eles)| eles.len() as usize).sum();
+        let n: usize = inner.values().map(|eles| eles.len()).sum();
 
         // instantiate vectors to fill
         let mut source_idx_res = Int32Array::builder(n);
@@ -37,28 +44,35 @@ impl Anime {
         let mut shared_len_res = Float64Array::builder(n);
         let mut source_weighted_res = Float64Array::builder(n);
         let mut target_weighted_res = Float64Array::builder(n);
+        let mut aligned_res = BooleanArray::builder(n);
+        let mut confidence_res = Float64Array::builder(n);
 
         for (target, items) in inner.iter() {
             let source_lens = &self.source_lens;
-            let target_len = self.target_lens.get(*target as usize).unwrap();
+            let target_len = self.target_lens.get(*target).unwrap();
 
-            for MatchCandidate {
-                source_index,
-                shared_len,
-            } in items.iter()
-            {
+            for mc in items.iter() {
+                let MatchCandidate {
+                    source_index,
+                    shared_len,
+                    aligned_len,
+                    ..
+                } = mc;
                 let source_len = *source_lens.get(*source_index).unwrap();
                 let target_id = *target as i32;
                 let source_id = *source_index as i32;
-                let shared_len = shared_len;
                 let source_weighted = shared_len / source_len;
                 let target_weighted = shared_len / target_len;
+                // aligned when the majority of the shared length runs the same direction
+                let aligned = *aligned_len >= shared_len - aligned_len;
 
                 shared_len_res.append_value(*shared_len);
                 source_idx_res.append_value(source_id);
                 target_idx_res.append_value(target_id);
                 source_weighted_res.append_value(source_weighted);
                 target_weighted_res.append_value(target_weighted);
+                aligned_res.append_value(aligned);
+                confidence_res.append_value(self.confidence(*target, mc));
             }
         }
 
@@ -70,9 +84,81 @@ impl Anime {
                 Arc::new(shared_len_res.finish()),
                 Arc::new(source_weighted_res.finish()),
                 Arc::new(target_weighted_res.finish()),
+                Arc::new(aligned_res.finish()),
+                Arc::new(confidence_res.finish()),
             ],
         )
         .expect("All arrays should be identical lengths");
         Ok(res)
     }
 }
+
+/// Read the (source, target) pair of each row of a match table
+///
+/// The table must contain integer `source_id` and `target_id` columns
+/// without nulls, as returned by [`Anime::get_matches`].
+pub(crate) fn read_match_ids(table: &RecordBatch) -> Result<Vec<(usize, usize)>, AnimeError> {
+    let ids = |name: &str| {
+        let col = table
+            .column_by_name(name)
+            .ok_or_else(|| AnimeError::InvalidMatchTable(format!("missing `{name}` column")))?;
+        if col.null_count() > 0 {
+            return Err(AnimeError::InvalidMatchTable(format!(
+                "`{name}` contains nulls"
+            )));
+        }
+        let col = arrow::compute::cast(col, &DataType::Int64).map_err(|_| {
+            AnimeError::InvalidMatchTable(format!("`{name}` must contain integer ids"))
+        })?;
+        col.as_primitive::<Int64Type>()
+            .values()
+            .iter()
+            .map(|id| {
+                usize::try_from(*id).map_err(|_| {
+                    AnimeError::InvalidMatchTable(format!("`{name}` contains negative ids"))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    let source_ids = ids("source_id")?;
+    let target_ids = ids("target_id")?;
+    Ok(source_ids.into_iter().zip(target_ids).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{Field, Schema};
+
+    #[test]
+    fn test_read_match_ids() {
+        let schema = Schema::new(vec![
+            Field::new("source_id", DataType::Int64, false),
+            Field::new("target_id", DataType::Int64, false),
+        ]);
+        let table = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int64Array::from(vec![0, 1, 1])),
+                Arc::new(Int64Array::from(vec![2, 0, 0])),
+            ],
+        )
+        .unwrap();
+
+        let pairs = read_match_ids(&table).unwrap();
+        assert_eq!(pairs, vec![(0, 2), (1, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn test_read_match_ids_missing_column() {
+        let schema = Schema::new(vec![Field::new("source_id", DataType::Int32, false)]);
+        let table =
+            RecordBatch::try_new(Arc::new(schema), vec![Arc::new(Int32Array::from(vec![0]))])
+                .unwrap();
+
+        let err = read_match_ids(&table).unwrap_err();
+        assert!(err.to_string().contains("target_id"));
+    }
+}
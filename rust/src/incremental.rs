@@ -0,0 +1,382 @@
+use crate::structs::TarLine;
+use crate::tolerance::FeatureTolerances;
+use crate::{
+    compare_components, insert_match, prepare_lines, Anime, AnimeError, ComponentData, MatchesMap,
+    Side, TargetTree,
+};
+use rstar::primitives::{CachedEnvelope, GeomWithData};
+use rstar::{RTree, RTreeObject, SelectionFunction};
+use std::collections::BTreeSet;
+
+impl Anime {
+    /// Add a source `LineString` and return its index
+    ///
+    /// The new source is indexed with `self.load_options`. When matches
+    /// have already been found, only the matches of the new source are
+    /// added. With per-feature tolerances, the new source uses
+    /// `distance_tolerance` and `angle_tolerance`. When that is larger than
+    /// every other source's distance tolerance, the targets are padded
+    /// again so that none of the new source's matches are missed.
+    pub fn insert_source(&mut self, x: geo_types::LineString) -> usize {
+        let i = self.source_lens.len();
+        let (len, components) = prepare_lines(x, &self.load_options);
+        self.source_lens.push(len);
+        if let Some(ft) = self.feature_tolerances.as_mut() {
+            let source_max = ft
+                .source_distance
+                .as_ref()
+                .map(|v| v.iter().copied().fold(f64::NEG_INFINITY, f64::max));
+            ft.source_distance
+                .iter_mut()
+                .for_each(|v| v.push(self.distance_tolerance));
+            ft.source_angle
+                .iter_mut()
+                .for_each(|v| v.push(self.angle_tolerance));
+
+            let is_wider = source_max.is_some_and(|m| self.distance_tolerance > m);
+            if is_wider {
+                let padding = ft.target_padding(self.distance_tolerance);
+                self.target_tree = repad_targets(&self.target_tree, padding);
+            }
+        }
+
+        for (line, slope, k) in components {
+            let data = (i, slope, k);
+            if let Some(matches) = self.matches.get_mut() {
+                for cy in self
+                    .target_tree
+                    .locate_in_envelope_intersecting(&line.envelope())
+                {
+                    let (angle, distance) = pair_tolerances(
+                        self.feature_tolerances.as_ref(),
+                        i,
+                        cy.data.0,
+                        self.angle_tolerance,
+                        self.distance_tolerance,
+                    );
+                    if let Some((cmp, segment)) = compare_components(
+                        &line,
+                        data,
+                        &cy.geom().0,
+                        cy.data,
+                        angle,
+                        distance,
+                        self.directed,
+                    ) {
+                        let segment = segment.filter(|_| self.record_segments);
+                        insert_match(matches, i, cy.data.0, &cmp, segment);
+                    }
+                }
+            }
+            self.source_tree
+                .insert(GeomWithData::new(CachedEnvelope::new(line), data));
+        }
+        i
+    }
+
+    /// Add a target `LineString` and return its index
+    ///
+    /// See [`Anime::insert_source`].
+    pub fn insert_target(&mut self, y: geo_types::LineString) -> usize {
+        let j = self.target_lens.len();
+        let (len, components) = prepare_lines(y, &self.load_options);
+        self.target_lens.push(len);
+        if let Some(ft) = self.feature_tolerances.as_mut() {
+            ft.target_distance
+                .iter_mut()
+                .for_each(|v| v.push(self.distance_tolerance));
+            ft.target_angle
+                .iter_mut()
+                .for_each(|v| v.push(self.angle_tolerance));
+        }
+        let padding = match self.feature_tolerances.as_ref() {
+            Some(ft) => ft.target_padding(self.distance_tolerance)(j),
+            None => self.distance_tolerance,
+        };
+
+        for (line, slope, k) in components {
+            let data = (j, slope, k);
+            let tl = TarLine(line, padding);
+            if let Some(matches) = self.matches.get_mut() {
+                for cx in self
+                    .source_tree
+                    .locate_in_envelope_intersecting(&tl.envelope())
+                {
+                    let (angle, distance) = pair_tolerances(
+                        self.feature_tolerances.as_ref(),
+                        cx.data.0,
+                        j,
+                        self.angle_tolerance,
+                        self.distance_tolerance,
+                    );
+                    if let Some((cmp, segment)) = compare_components(
+                        cx.geom(),
+                        cx.data,
+                        &line,
+                        data,
+                        angle,
+                        distance,
+                        self.directed,
+                    ) {
+                        let segment = segment.filter(|_| self.record_segments);
+                        insert_match(matches, cx.data.0, j, &cmp, segment);
+                    }
+                }
+            }
+            self.target_tree
+                .insert(GeomWithData::new(CachedEnvelope::new(tl), data));
+        }
+        j
+    }
+
+    /// Remove a source without renumbering the others
+    ///
+    /// The source's component lines and matches are removed and its length
+    /// is set to `0.0` so it is treated like an empty geometry. The index is
+    /// never reused. Removing a source twice has no effect.
+    pub fn remove_source(&mut self, i: usize) -> Result<(), AnimeError> {
+        if i >= self.source_lens.len() {
+            return Err(AnimeError::FeatureNotFound {
+                side: Side::Source,
+                index: i,
+            });
+        }
+        let removed = self
+            .source_tree
+            .drain_with_selection_function(SelectFeature(i))
+            .collect::<Vec<_>>();
+        self.source_lens[i] = 0.0;
+
+        if let Some(matches) = self.matches.get_mut() {
+            // only targets near the removed component lines can be matched
+            let targets = removed
+                .iter()
+                .flat_map(|cx| {
+                    self.target_tree
+                        .locate_in_envelope_intersecting(&cx.envelope())
+                        .map(|cy| cy.data.0)
+                })
+                .collect::<BTreeSet<_>>();
+            for j in targets {
+                remove_candidate(matches, i, j);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a target without renumbering the others
+    ///
+    /// See [`Anime::remove_source`].
+    pub fn remove_target(&mut self, j: usize) -> Result<(), AnimeError> {
+        if j >= self.target_lens.len() {
+            return Err(AnimeError::FeatureNotFound {
+                side: Side::Target,
+                index: j,
+            });
+        }
+        self.target_tree
+            .drain_with_selection_function(SelectFeature(j))
+            .for_each(drop);
+        self.target_lens[j] = 0.0;
+
+        if let Some(matches) = self.matches.get_mut() {
+            matches.remove(&j);
+        }
+        Ok(())
+    }
+}
+
+// rebuild a target tree padding each target by its own distance
+fn repad_targets(tree: &TargetTree, padding: impl Fn(usize) -> f64) -> TargetTree {
+    let to_insert = tree
+        .iter()
+        .map(|cy| {
+            let tl = TarLine(cy.geom().0, padding(cy.data.0));
+            GeomWithData::new(CachedEnvelope::new(tl), cy.data)
+        })
+        .collect::<Vec<_>>();
+    RTree::bulk_load(to_insert)
+}
+
+// selects every component line of one feature
+struct SelectFeature(usize);
+
+impl<T: RTreeObject> SelectionFunction<GeomWithData<T, ComponentData>> for SelectFeature {
+    fn should_unpack_parent(&self, _envelope: &T::Envelope) -> bool {
+        true
+    }
+
+    fn should_unpack_leaf(&self, leaf: &GeomWithData<T, ComponentData>) -> bool {
+        leaf.data.0 == self.0
+    }
+}
+
+fn pair_tolerances(
+    feature_tolerances: Option<&FeatureTolerances>,
+    i: usize,
+    j: usize,
+    angle_tolerance: f64,
+    distance_tolerance: f64,
+) -> (f64, f64) {
+    match feature_tolerances {
+        Some(ft) => (
+            ft.angle(i, j, angle_tolerance),
+            ft.distance(i, j, distance_tolerance),
+        ),
+        None => (angle_tolerance, distance_tolerance),
+    }
+}
+
+fn remove_candidate(matches: &mut MatchesMap, i: usize, j: usize) {
+    if let Some(items) = matches.get_mut(&j) {
+        items.retain(|mc| mc.source_index != i);
+        if items.is_empty() {
+            matches.remove(&j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoadOptions;
+    use geo_types::{coord, LineString};
+
+    fn line(x0: f64, x1: f64, y: f64) -> LineString {
+        LineString::new(vec![coord! {x: x0, y: y}, coord! {x: x1, y: y}])
+    }
+
+    fn shared(anime: &Anime) -> Vec<(usize, usize, f64)> {
+        let mut res = anime
+            .matches
+            .get()
+            .unwrap()
+            .iter()
+            .flat_map(|(j, items)| {
+                items
+                    .iter()
+                    .map(move |mc| (mc.source_index, *j, mc.shared_len))
+            })
+            .collect::<Vec<_>>();
+        res.sort_by_key(|p| (p.0, p.1));
+        res
+    }
+
+    #[test]
+    fn test_insert_same_as_new() {
+        let source = vec![line(0.0, 10.0, 0.5), line(10.0, 20.0, 0.5)];
+        let target = vec![line(0.0, 20.0, 0.0), line(5.0, 15.0, 1.0)];
+        let full = Anime::new(
+            source.clone().into_iter(),
+            target.clone().into_iter(),
+            1.0,
+            5.0,
+        );
+
+        let mut anime = Anime::new(
+            source[..1].iter().cloned(),
+            target[..1].iter().cloned(),
+            1.0,
+            5.0,
+        );
+        assert_eq!(anime.insert_source(source[1].clone()), 1);
+        assert_eq!(anime.insert_target(target[1].clone()), 1);
+
+        assert_eq!(shared(&anime), shared(&full));
+        assert_eq!(anime.source_lens, full.source_lens);
+        assert_eq!(anime.target_lens, full.target_lens);
+    }
+
+    #[test]
+    fn test_remove_keeps_ids() {
+        let source = vec![line(0.0, 10.0, 0.5), line(10.0, 20.0, 0.5)];
+        let target = vec![line(0.0, 20.0, 0.0), line(5.0, 15.0, 1.0)];
+        let mut anime = Anime::new(source.into_iter(), target.into_iter(), 1.0, 5.0);
+
+        anime.remove_source(0).unwrap();
+        assert!(shared(&anime).iter().all(|(i, _, _)| *i == 1));
+        assert_eq!(anime.source_lens, vec![0.0, 10.0]);
+        assert_eq!(anime.source_tree.size(), 1);
+
+        anime.remove_target(1).unwrap();
+        assert_eq!(shared(&anime), vec![(1, 0, 10.0)]);
+        assert_eq!(anime.target_lens.len(), 2);
+
+        // removed ids are not reused
+        assert_eq!(anime.insert_source(line(0.0, 5.0, 0.5)), 2);
+        assert_eq!(shared(&anime), vec![(1, 0, 10.0), (2, 0, 5.0)]);
+    }
+
+    #[test]
+    fn test_remove_missing() {
+        let mut anime = Anime::new(
+            vec![line(0.0, 10.0, 0.5)].into_iter(),
+            vec![line(0.0, 10.0, 0.0)].into_iter(),
+            1.0,
+            5.0,
+        );
+        assert!(matches!(
+            anime.remove_target(3),
+            Err(AnimeError::FeatureNotFound {
+                side: Side::Target,
+                index: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_insert_before_matching() {
+        let mut anime = Anime::load_geometries(
+            vec![line(0.0, 10.0, 0.5)].into_iter(),
+            std::iter::empty(),
+            1.0,
+            5.0,
+        );
+        anime.insert_target(line(0.0, 10.0, 0.0));
+        anime.find_matches().unwrap();
+        assert_eq!(shared(&anime), vec![(0, 0, 10.0)]);
+    }
+
+    #[test]
+    fn test_insert_wider_source_repads_targets() {
+        use crate::tolerance::FeatureTolerances;
+
+        let tols = FeatureTolerances {
+            source_distance: Some(vec![0.5]),
+            ..Default::default()
+        };
+        let mut anime = Anime::load_geometries_with_tolerances(
+            vec![line(0.0, 10.0, 0.25)].into_iter(),
+            vec![line(0.0, 10.0, 0.0)].into_iter(),
+            3.0,
+            5.0,
+            tols,
+        )
+        .unwrap();
+        anime.find_matches().unwrap();
+
+        // the new source takes the wider default distance tolerance
+        assert_eq!(anime.insert_source(line(0.0, 10.0, 2.5)), 1);
+        assert_eq!(shared(&anime), vec![(0, 0, 10.0), (1, 0, 10.0)]);
+    }
+
+    #[test]
+    fn test_insert_reuses_load_options() {
+        let options = LoadOptions {
+            max_segment_len: Some(2.0),
+            ..Default::default()
+        };
+        let mut anime = Anime::load_geometries_with_options(
+            std::iter::empty(),
+            std::iter::empty(),
+            1.0,
+            5.0,
+            options,
+        )
+        .unwrap();
+        anime.insert_source(line(0.0, 10.0, 0.5));
+        anime.insert_target(line(0.0, 10.0, 0.0));
+        assert_eq!(anime.source_tree.size(), 5);
+        assert_eq!(anime.target_tree.size(), 5);
+    }
+}
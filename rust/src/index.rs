@@ -0,0 +1,232 @@
+use crate::{
+    compare_components, create_target_rtree, insert_match, prepare_lines, repad_target_tree, Anime,
+    LoadOptions, MatchesMap, TargetTree,
+};
+use rstar::{RTreeObject, AABB};
+
+/// A target network indexed once and matched against sources on demand
+///
+/// Building the target R* Tree dominates the cost of matching a handful of
+/// sources. A `TargetSearchIndex` keeps the tree so new `LineString`s can be
+/// matched against it without rebuilding anything, for example while a
+/// user is editing a network.
+#[derive(Clone, Debug)]
+pub struct TargetSearchIndex {
+    pub distance_tolerance: f64,
+    pub angle_tolerance: f64,
+    /// See [`Anime::directed`]
+    pub directed: bool,
+    /// See [`Anime::record_segments`]
+    pub record_segments: bool,
+    /// The options the target was loaded with, also applied to each source.
+    /// See [`Anime::load_options`]
+    pub load_options: LoadOptions,
+    pub target_tree: TargetTree,
+    pub target_lens: Vec<f64>,
+}
+
+impl TargetSearchIndex {
+    /// Index target `LineString` geometries
+    pub fn new(
+        target: impl Iterator<Item = geo_types::LineString>,
+        distance_tolerance: f64,
+        angle_tolerance: f64,
+    ) -> Self {
+        let mut target_lens = Vec::new();
+        let load_options = LoadOptions::default();
+        let target_tree = create_target_rtree(
+            target,
+            &mut target_lens,
+            |_| distance_tolerance,
+            &load_options,
+        );
+        Self {
+            distance_tolerance,
+            angle_tolerance,
+            directed: false,
+            record_segments: false,
+            load_options,
+            target_tree,
+            target_lens,
+        }
+    }
+
+    /// Match a single source `LineString`
+    ///
+    /// The `source_index` of each candidate is `0`.
+    pub fn match_line(&self, source: geo_types::LineString) -> MatchesMap {
+        self.match_lines(std::iter::once(source))
+    }
+
+    /// Match a batch of source `LineString`s
+    ///
+    /// The `source_index` of each candidate is the position of the source
+    /// in the batch. The results are identical to those of
+    /// [`Anime::find_matches`] with the same target and tolerances.
+    pub fn match_lines(&self, source: impl Iterator<Item = geo_types::LineString>) -> MatchesMap {
+        let mut matches = MatchesMap::new();
+        for (i, xi) in source.enumerate() {
+            let (_, components) = prepare_lines(xi, &self.load_options);
+            for (x, slope, k) in components {
+                let env: AABB<_> = x.envelope();
+                for cy in self.target_tree.locate_in_envelope_intersecting(&env) {
+                    let y = &cy.geom().0;
+                    if let Some((cmp, segment)) = compare_components(
+                        &x,
+                        (i, slope, k),
+                        y,
+                        cy.data,
+                        self.angle_tolerance,
+                        self.distance_tolerance,
+                        self.directed,
+                    ) {
+                        let segment = segment.filter(|_| self.record_segments);
+                        insert_match(&mut matches, i, cy.data.0, &cmp, segment);
+                    }
+                }
+            }
+        }
+        matches
+    }
+}
+
+impl Anime {
+    /// A reusable index of the target
+    ///
+    /// Per-feature tolerances are not carried over: the target tree is
+    /// padded to the global `distance_tolerance` instead.
+    pub fn target_search_index(&self) -> TargetSearchIndex {
+        let target_tree = match self.feature_tolerances {
+            Some(_) => repad_target_tree(&self.target_tree, self.distance_tolerance),
+            None => self.target_tree.clone(),
+        };
+        TargetSearchIndex {
+            distance_tolerance: self.distance_tolerance,
+            angle_tolerance: self.angle_tolerance,
+            directed: self.directed,
+            record_segments: self.record_segments,
+            load_options: self.load_options.clone(),
+            target_tree,
+            target_lens: self.target_lens.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FeatureTolerances;
+    use geo_types::{coord, LineString};
+
+    fn target() -> Vec<LineString> {
+        vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 10.0, y: 0.0}, coord! {x: 10.0, y: 10.0}]),
+        ]
+    }
+
+    #[test]
+    fn test_match_line() {
+        let index = TargetSearchIndex::new(target().into_iter(), 1.0, 5.0);
+        let source = LineString::new(vec![
+            coord! {x: 2.0, y: 0.5},
+            coord! {x: 9.5, y: 0.5},
+            coord! {x: 9.5, y: 5.0},
+        ]);
+        let res = index.match_line(source);
+
+        assert_eq!(res.len(), 2);
+        assert!((res[&0][0].shared_len - 7.5).abs() < 1e-9);
+        assert!((res[&1][0].shared_len - 4.5).abs() < 1e-9);
+        assert_eq!(res[&1][0].source_index, 0);
+    }
+
+    fn assert_same_matches(res: &MatchesMap, expected: &MatchesMap) {
+        assert_eq!(
+            res.keys().collect::<Vec<_>>(),
+            expected.keys().collect::<Vec<_>>()
+        );
+        for (j, items) in expected.iter() {
+            let mut found = res[j]
+                .iter()
+                .map(|mc| (mc.source_index, mc.shared_len))
+                .collect::<Vec<_>>();
+            let mut wanted = items
+                .iter()
+                .map(|mc| (mc.source_index, mc.shared_len))
+                .collect::<Vec<_>>();
+            found.sort_by_key(|p| p.0);
+            wanted.sort_by_key(|p| p.0);
+            assert_eq!(found.len(), wanted.len());
+            for (f, w) in found.iter().zip(wanted.iter()) {
+                assert_eq!(f.0, w.0);
+                assert!((f.1 - w.1).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_match_lines_same_as_anime() {
+        let source = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.5}, coord! {x: 10.0, y: 0.5}]),
+            LineString::new(vec![coord! {x: 9.5, y: 0.0}, coord! {x: 9.5, y: 8.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 50.0}, coord! {x: 9.0, y: 50.0}]),
+        ];
+        let anime = Anime::new(source.clone().into_iter(), target().into_iter(), 1.0, 5.0);
+        let res = anime.target_search_index().match_lines(source.into_iter());
+
+        assert_same_matches(&res, anime.matches.get().unwrap());
+    }
+
+    #[test]
+    fn test_match_lines_uses_load_options() {
+        // each zigzag segment is too steep to match but the simplified line is not
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.5},
+            coord! {x: 2.0, y: 0.8},
+            coord! {x: 4.0, y: 0.5},
+            coord! {x: 6.0, y: 0.8},
+            coord! {x: 8.0, y: 0.5},
+            coord! {x: 10.0, y: 0.8},
+        ])];
+        let options = LoadOptions {
+            simplify_tolerance: Some(0.5),
+            ..Default::default()
+        };
+        let mut anime = Anime::load_geometries_with_options(
+            source.clone().into_iter(),
+            target().into_iter(),
+            1.0,
+            5.0,
+            options,
+        )
+        .unwrap();
+        anime.find_matches().unwrap();
+        let expected = anime.matches.get().unwrap();
+        assert!(expected.contains_key(&0));
+
+        let res = anime.target_search_index().match_lines(source.into_iter());
+        assert_same_matches(&res, expected);
+    }
+
+    #[test]
+    fn test_target_search_index_repads_feature_tolerances() {
+        let tols = FeatureTolerances {
+            target_distance: Some(vec![0.1, 0.1]),
+            ..Default::default()
+        };
+        let anime = Anime::load_geometries_with_tolerances(
+            std::iter::empty(),
+            target().into_iter(),
+            1.0,
+            5.0,
+            tols,
+        )
+        .unwrap();
+        let source = LineString::new(vec![coord! {x: 0.0, y: 0.5}, coord! {x: 10.0, y: 0.5}]);
+        let res = anime.target_search_index().match_line(source);
+
+        // matched with the global distance tolerance
+        assert!((res[&0][0].shared_len - 10.0).abs() < 1e-9);
+    }
+}
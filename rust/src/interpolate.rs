@@ -2,7 +2,7 @@ use core::f64;
 
 use arrow::array::{Array, Float64Array};
 
-use crate::{Anime, AnimeError};
+use crate::{Anime, AnimeError, MatchCandidate};
 
 /// Intensive or Extensive Interpolation
 ///
@@ -16,16 +16,148 @@ pub enum Tensive {
     Ex,
 }
 
+/// A distance-decay kernel applied to lateral offsets
+///
+/// Each kernel maps an offset $d \ge 0$ to a weight so that sources
+/// closer to the target contribute more to the interpolated value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecayKernel {
+    /// $\max(0, 1 - d / bandwidth)$
+    Linear { bandwidth: f64 },
+    /// $\exp(-\frac{1}{2} (d / bandwidth)^2)$
+    Gaussian { bandwidth: f64 },
+    /// $(min\_distance / \max(d, min\_distance))^{power}$, normalised so
+    /// that offsets up to `min_distance` have a weight of 1
+    InverseDistance { power: f64, min_distance: f64 },
+}
+
+impl DecayKernel {
+    /// Check that the kernel parameters produce finite weights
+    ///
+    /// Bandwidths and `min_distance` must be finite and positive and
+    /// `power` must be finite and non-negative.
+    pub fn validate(&self) -> Result<(), AnimeError> {
+        let is_positive = |x: f64| x.is_finite() && x > 0.0;
+        match *self {
+            DecayKernel::Linear { bandwidth } | DecayKernel::Gaussian { bandwidth } => {
+                if !is_positive(bandwidth) {
+                    return Err(AnimeError::InvalidKernel(
+                        "`bandwidth` must be finite and positive",
+                    ));
+                }
+            }
+            DecayKernel::InverseDistance {
+                power,
+                min_distance,
+            } => {
+                if !power.is_finite() || power < 0.0 {
+                    return Err(AnimeError::InvalidKernel(
+                        "`power` must be finite and non-negative",
+                    ));
+                }
+                if !is_positive(min_distance) {
+                    return Err(AnimeError::InvalidKernel(
+                        "`min_distance` must be finite and positive",
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The weight of a lateral offset
+    pub fn weight(&self, offset: f64) -> f64 {
+        match *self {
+            DecayKernel::Linear { bandwidth } => (1.0 - offset / bandwidth).max(0.0),
+            DecayKernel::Gaussian { bandwidth } => (-0.5 * (offset / bandwidth).powi(2)).exp(),
+            DecayKernel::InverseDistance {
+                power,
+                min_distance,
+            } => {
+                if offset <= min_distance {
+                    1.0
+                } else {
+                    (min_distance / offset).powf(power)
+                }
+            }
+        }
+    }
+}
+
+impl MatchCandidate {
+    /// The length weighted mean kernel weight of the matched segments
+    ///
+    /// Each segment pair is weighted by the kernel applied to its mean
+    /// lateral offset. Returns `1.0` when no segments share a portion.
+    pub fn decay(&self, kernel: &DecayKernel) -> f64 {
+        let (weighted, total) = self.segments.iter().fold((0.0, 0.0), |(w, t), s| {
+            let len = s.shared_len();
+            (w + len * kernel.weight(s.offset()), t + len)
+        });
+        if total > 0.0 {
+            weighted / total
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Adjustments to the shared length used when interpolating
+///
+/// By default the shared length $SL_{ij}$ is used as is. Each enabled
+/// adjustment multiplies it.
+#[derive(Debug, Clone, Default)]
+pub struct InterpolationWeights {
+    /// Multiply the shared length by the match confidence
+    /// scored with the `Anime`'s `confidence_model`
+    pub confidence: bool,
+    /// Multiply the shared length by the distance-decay of the match.
+    /// Requires [`Anime::record_segments`]. See [`MatchCandidate::decay`].
+    pub kernel: Option<DecayKernel>,
+}
+
+impl InterpolationWeights {
+    /// Check that the kernel, if any, is valid
+    fn validate(&self) -> Result<(), AnimeError> {
+        match &self.kernel {
+            Some(kernel) => kernel.validate(),
+            None => Ok(()),
+        }
+    }
+
+    /// The adjusted shared length of a match with target `target_idx`
+    pub fn shared_len(&self, anime: &Anime, target_idx: usize, mc: &MatchCandidate) -> f64 {
+        let mut shared_len = mc.shared_len;
+        if self.confidence {
+            shared_len *= anime.confidence(target_idx, mc);
+        }
+        if let Some(kernel) = &self.kernel {
+            shared_len *= mc.decay(kernel);
+        }
+        shared_len
+    }
+}
+
 impl Anime {
     /// Perform numeric attribute interpolation
     pub fn interpolate(
         &self,
         var: &Float64Array,
         tensive: Tensive,
+    ) -> Result<Float64Array, AnimeError> {
+        self.interpolate_with(var, tensive, &InterpolationWeights::default())
+    }
+
+    /// Perform numeric attribute interpolation with adjusted weights
+    pub fn interpolate_with(
+        &self,
+        var: &Float64Array,
+        tensive: Tensive,
+        weights: &InterpolationWeights,
     ) -> Result<Float64Array, AnimeError> {
         match tensive {
-            Tensive::In => self.interpolate_intensive(var),
-            Tensive::Ex => self.interpolate_extensive(var),
+            Tensive::In => self.interpolate_intensive_with(var, weights),
+            Tensive::Ex => self.interpolate_extensive_with(var, weights),
         }
     }
 
@@ -42,6 +174,21 @@ impl Anime {
     pub fn interpolate_extensive(
         &self,
         source_var: &Float64Array,
+    ) -> Result<Float64Array, AnimeError> {
+        self.interpolate_extensive_with(source_var, &InterpolationWeights::default())
+    }
+
+    /// Extensive Interpolation with adjusted weights
+    ///
+    /// The adjusted shared lengths of each source are rescaled to sum to
+    /// its unadjusted shared length, so the adjustments redistribute a
+    /// source between its targets without changing the total it contributes.
+    ///
+    /// See [`Anime::interpolate_extensive`] and [`InterpolationWeights`].
+    pub fn interpolate_extensive_with(
+        &self,
+        source_var: &Float64Array,
+        weights: &InterpolationWeights,
     ) -> Result<Float64Array, AnimeError> {
         // Check if `source_var` matches the number of source geometries
         if source_var.len() != self.source_lens.len() {
@@ -54,13 +201,37 @@ impl Anime {
 
         // Retrieve matches (or return error if not found)
         let matches_map = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+        weights.validate()?;
+        if weights.kernel.is_some() {
+            self.require_segments()?;
+        }
+
+        // Adjusted weights are normalised per source so that each source
+        // still hands out the same total, shifted towards the targets with
+        // the larger adjusted shared length
+        let mut raw_totals = vec![0.0; self.source_lens.len()];
+        let mut adjusted_totals = vec![0.0; self.source_lens.len()];
+        for (&target_idx, matches) in matches_map.iter() {
+            for mi in matches {
+                raw_totals[mi.source_index] += mi.shared_len;
+                adjusted_totals[mi.source_index] += weights.shared_len(self, target_idx, mi);
+            }
+        }
 
         // Interpolate extensive variable
         let res = (0..self.target_lens.len()).map(|target_idx| {
             if let Some(matches) = matches_map.get(&target_idx) {
                 let value = matches.iter().fold(0.0, |acc, mi| {
                     let source_idx = mi.source_index;
-                    let shared_len = mi.shared_len;
+                    let adjusted_total = adjusted_totals[source_idx];
+                    // fall back to the raw shared length when every
+                    // adjusted weight of the source is zero
+                    let shared_len = if adjusted_total > 0.0 {
+                        weights.shared_len(self, target_idx, mi)
+                            * (raw_totals[source_idx] / adjusted_total)
+                    } else {
+                        mi.shared_len
+                    };
 
                     // Weight = shared length / total length of source geometry
                     let wt = shared_len / self.source_lens[source_idx];
@@ -106,6 +277,17 @@ impl Anime {
     pub fn interpolate_intensive(
         &self,
         source_var: &Float64Array,
+    ) -> Result<Float64Array, AnimeError> {
+        self.interpolate_intensive_with(source_var, &InterpolationWeights::default())
+    }
+
+    /// Intensive Interpolation with adjusted weights
+    ///
+    /// See [`Anime::interpolate_intensive`] and [`InterpolationWeights`].
+    pub fn interpolate_intensive_with(
+        &self,
+        source_var: &Float64Array,
+        weights: &InterpolationWeights,
     ) -> Result<Float64Array, AnimeError> {
         let nv = source_var.len();
         let n_tar = self.source_lens.len(); // Assuming target_lens represent target lengths.
@@ -120,6 +302,10 @@ impl Anime {
 
         // Ensure matches are loaded
         let matches_map = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+        weights.validate()?;
+        if weights.kernel.is_some() {
+            self.require_segments()?;
+        }
 
         let res = (0..self.target_lens.len()).map(|target_idx| {
             if let Some(matches) = matches_map.get(&target_idx) {
@@ -129,7 +315,8 @@ impl Anime {
                         let source_idx = mi.source_index;
 
                         // Weight based on shared length and target length
-                        let wt = mi.shared_len / self.target_lens[target_idx];
+                        let shared_len = weights.shared_len(self, target_idx, mi);
+                        let wt = shared_len / self.target_lens[target_idx];
                         let sv = source_var.value(source_idx);
 
                         // here we handle NA values and NaN by skipping them
@@ -317,6 +504,165 @@ mod tests {
         assert_eq!(result_array.len(), 1);
     }
 
+    #[test]
+    fn test_interpolate_intensive_confidence_weights() {
+        let source: Vec<LineString> = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 0.9}, coord! {x: 10.0, y: 0.9}]),
+        ];
+        let target: Vec<LineString> = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.1},
+            coord! {x: 10.0, y: 0.1},
+        ])];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+        let source_var = Float64Array::from(vec![10.0, 20.0]);
+
+        let unweighted = anime.interpolate_intensive(&source_var).unwrap();
+        assert_eq!(unweighted.value(0), 15.0);
+
+        let weights = InterpolationWeights {
+            confidence: true,
+            ..Default::default()
+        };
+        let weighted = anime
+            .interpolate_with(&source_var, Tensive::In, &weights)
+            .unwrap();
+        // the nearer source dominates
+        assert!(weighted.value(0) < 15.0);
+    }
+
+    #[test]
+    fn test_decay_kernel_weights() {
+        let linear = DecayKernel::Linear { bandwidth: 2.0 };
+        assert_eq!(linear.weight(0.0), 1.0);
+        assert_eq!(linear.weight(1.0), 0.5);
+        assert_eq!(linear.weight(3.0), 0.0);
+
+        let gaussian = DecayKernel::Gaussian { bandwidth: 1.0 };
+        assert_eq!(gaussian.weight(0.0), 1.0);
+        assert!((gaussian.weight(1.0) - (-0.5f64).exp()).abs() < 1e-12);
+
+        let idw = DecayKernel::InverseDistance {
+            power: 2.0,
+            min_distance: 0.5,
+        };
+        assert_eq!(idw.weight(1.0), 0.25);
+        // offsets below min_distance are clamped
+        assert_eq!(idw.weight(0.0), 1.0);
+        assert_eq!(idw.weight(0.5), 1.0);
+    }
+
+    #[test]
+    fn test_interpolate_extensive_inverse_distance_bounded() {
+        let source: Vec<LineString> = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target: Vec<LineString> = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.1},
+            coord! {x: 10.0, y: 0.1},
+        ])];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+        let source_var = Float64Array::from(vec![10.0]);
+
+        let weights = InterpolationWeights {
+            kernel: Some(DecayKernel::InverseDistance {
+                power: 2.0,
+                min_distance: 0.5,
+            }),
+            ..Default::default()
+        };
+        let res = anime
+            .interpolate_with(&source_var, Tensive::Ex, &weights)
+            .unwrap();
+        // the target cannot receive more than the source total
+        assert!((res.value(0) - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_interpolate_extensive_decay_kernel() {
+        let source: Vec<LineString> = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target: Vec<LineString> = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.2}, coord! {x: 10.0, y: 0.2}]),
+            LineString::new(vec![coord! {x: 0.0, y: -0.8}, coord! {x: 10.0, y: -0.8}]),
+        ];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+        let source_var = Float64Array::from(vec![10.0]);
+
+        let unweighted = anime.interpolate_extensive(&source_var).unwrap();
+        assert!((unweighted.value(0) - 10.0).abs() < 1e-9);
+        assert!((unweighted.value(1) - 10.0).abs() < 1e-9);
+
+        let weights = InterpolationWeights {
+            kernel: Some(DecayKernel::Linear { bandwidth: 1.0 }),
+            ..Default::default()
+        };
+        let decayed = anime
+            .interpolate_with(&source_var, Tensive::Ex, &weights)
+            .unwrap();
+        // 20 split 0.8 : 0.2
+        assert!((decayed.value(0) - 16.0).abs() < 1e-9);
+        assert!((decayed.value(1) - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_interpolate_extensive_weights_conserve_total() {
+        let source: Vec<LineString> = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 1.0}, coord! {x: 10.0, y: 1.0}]),
+        ];
+        let target: Vec<LineString> = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.2}, coord! {x: 10.0, y: 0.2}]),
+            LineString::new(vec![coord! {x: 0.0, y: 0.7}, coord! {x: 10.0, y: 0.7}]),
+        ];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+        let source_var = Float64Array::from(vec![10.0, 20.0]);
+        let total = |res: &Float64Array| res.values().iter().sum::<f64>();
+
+        let unweighted = anime.interpolate_extensive(&source_var).unwrap();
+        let weights = InterpolationWeights {
+            confidence: true,
+            kernel: Some(DecayKernel::Gaussian { bandwidth: 0.5 }),
+        };
+        let weighted = anime
+            .interpolate_with(&source_var, Tensive::Ex, &weights)
+            .unwrap();
+        assert!((total(&weighted) - total(&unweighted)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_interpolate_invalid_kernel() {
+        let anime = create_test_anime();
+        let source_var = Float64Array::from(vec![10.0, 20.0]);
+
+        for kernel in [
+            DecayKernel::Gaussian { bandwidth: 0.0 },
+            DecayKernel::Linear {
+                bandwidth: f64::NAN,
+            },
+            DecayKernel::InverseDistance {
+                power: -1.0,
+                min_distance: 0.5,
+            },
+            DecayKernel::InverseDistance {
+                power: 2.0,
+                min_distance: 0.0,
+            },
+        ] {
+            let weights = InterpolationWeights {
+                kernel: Some(kernel),
+                ..Default::default()
+            };
+            for tensive in [Tensive::In, Tensive::Ex] {
+                let res = anime.interpolate_with(&source_var, tensive, &weights);
+                assert!(matches!(res, Err(AnimeError::InvalidKernel(_))));
+            }
+        }
+    }
+
     #[test]
     fn test_interpolate_handles_f64_max() {
         let anime = create_test_anime();
@@ -0,0 +1,526 @@
+use crate::get_matches::read_match_ids;
+use crate::{Anime, AnimeError, MatchCandidate, MatchesMap};
+use arrow::array::{Array, ArrayRef, AsArray, Float64Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Names of the per-pair features in the order they are computed
+pub const FEATURE_NAMES: [&str; 6] = [
+    "shared_len",
+    "source_weighted",
+    "target_weighted",
+    "mean_offset",
+    "mean_angle_diff",
+    "length_ratio",
+];
+
+/// The features of a single (source, target) pair
+pub type PairFeatures = [f64; FEATURE_NAMES.len()];
+
+impl Anime {
+    /// Compute the features of a match with target `target_index`
+    ///
+    /// The features are, in order, the `shared_len`, the `shared_len`
+    /// weighted by the source and target lengths, the mean lateral offset,
+    /// the mean angle difference, and the ratio of the shorter to the longer
+    /// geometry. Pairs that share no portion of their component lines take
+    /// the pair's distance and angle tolerances as their offset and angle.
+    ///
+    /// The offset and angle are only known when [`Anime::record_segments`]
+    /// is set, so extracting, training on, or filtering by the features of
+    /// every match returns [`AnimeError::SegmentsNotRecorded`] otherwise.
+    pub fn pair_features(&self, target_index: usize, mc: &MatchCandidate) -> PairFeatures {
+        let i = mc.source_index;
+        let source_len = self.source_lens[i];
+        let target_len = self.target_lens[target_index];
+
+        let (mean_offset, mean_angle_diff) =
+            mc.mean_offset_and_angle()
+                .unwrap_or_else(|| match &self.feature_tolerances {
+                    Some(ft) => (
+                        ft.distance(i, target_index, self.distance_tolerance),
+                        ft.angle(i, target_index, self.angle_tolerance),
+                    ),
+                    None => (self.distance_tolerance, self.angle_tolerance),
+                });
+
+        [
+            mc.shared_len,
+            mc.shared_len / source_len,
+            mc.shared_len / target_len,
+            mean_offset,
+            mean_angle_diff,
+            source_len.min(target_len) / source_len.max(target_len),
+        ]
+    }
+
+    /// Extract the features of every match as a `RecordBatch`
+    ///
+    /// Contains `source_id`, `target_id`, and a column for each of
+    /// [`FEATURE_NAMES`]. Rows are in the same order as [`Anime::get_matches`].
+    pub fn get_match_features(&self) -> Result<RecordBatch, AnimeError> {
+        let base = self.get_matches()?;
+        let features = self.all_features()?;
+
+        let mut fields = base.schema().fields()[..2].to_vec();
+        let mut columns = base.columns()[..2].to_vec();
+        for (k, name) in FEATURE_NAMES.iter().enumerate() {
+            fields.push(Arc::new(Field::new(*name, DataType::Float64, false)));
+            let col: ArrayRef = Arc::new(Float64Array::from_iter_values(
+                features.iter().map(|(_, _, f)| f[k]),
+            ));
+            columns.push(col);
+        }
+
+        let res = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+            .expect("All arrays should be identical lengths");
+        Ok(res)
+    }
+
+    /// Train a [`DecisionTree`] from a labelled match table
+    ///
+    /// `labels` contains the `source_id` and `target_id` of reviewed pairs
+    /// and, optionally, a boolean `label` column that is `true` for correct
+    /// matches and `false` for incorrect ones. With a `label` column only
+    /// the labelled matches found by `self` are training examples and rows
+    /// with a null `label` are skipped. Without one, `labels` lists verified
+    /// matches and every match found by `self` is a training example: it is
+    /// positive when it appears in `labels` and negative otherwise.
+    pub fn train_acceptance(
+        &self,
+        labels: &RecordBatch,
+        params: &TreeParams,
+    ) -> Result<DecisionTree, AnimeError> {
+        let (truth, unlisted_negative) = read_labels(labels)?;
+        let (x, y): (Vec<_>, Vec<_>) = self
+            .all_features()?
+            .into_iter()
+            .filter_map(|(i, j, f)| match truth.get(&(i, j)) {
+                Some(label) => Some((f, *label)),
+                None => unlisted_negative.then_some((f, false)),
+            })
+            .unzip();
+        Ok(DecisionTree::fit(&x, &y, params))
+    }
+
+    /// Extract the matches with their probability of acceptance
+    ///
+    /// Contains the columns of [`Anime::get_matches`] followed by
+    /// `p_accept` predicted by `model`.
+    pub fn get_matches_with_acceptance(
+        &self,
+        model: &DecisionTree,
+    ) -> Result<RecordBatch, AnimeError> {
+        let base = self.get_matches()?;
+        let p_accept = self
+            .all_features()?
+            .iter()
+            .map(|(_, _, f)| model.predict(f))
+            .collect::<Float64Array>();
+
+        let mut fields = base.schema().fields().to_vec();
+        let mut columns = base.columns().to_vec();
+        fields.push(Arc::new(Field::new("p_accept", DataType::Float64, false)));
+        columns.push(Arc::new(p_accept));
+
+        let res = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+            .expect("All arrays should be identical lengths");
+        Ok(res)
+    }
+
+    /// The matches whose probability of acceptance is at least `threshold`
+    pub fn accepted_matches(
+        &self,
+        model: &DecisionTree,
+        threshold: f64,
+    ) -> Result<MatchesMap, AnimeError> {
+        let inner = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+        self.require_segments()?;
+
+        let mut res = MatchesMap::new();
+        for (j, items) in inner.iter() {
+            let accepted = items
+                .iter()
+                .filter(|mc| model.predict(&self.pair_features(*j, mc)) >= threshold)
+                .cloned()
+                .collect::<Vec<_>>();
+            if !accepted.is_empty() {
+                res.insert(*j, accepted);
+            }
+        }
+        Ok(res)
+    }
+
+    fn all_features(&self) -> Result<Vec<(usize, usize, PairFeatures)>, AnimeError> {
+        let inner = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+        self.require_segments()?;
+        let res = inner
+            .iter()
+            .flat_map(|(j, items)| {
+                items
+                    .iter()
+                    .map(move |mc| (mc.source_index, *j, self.pair_features(*j, mc)))
+            })
+            .collect();
+        Ok(res)
+    }
+}
+
+type Labels = BTreeMap<(usize, usize), bool>;
+
+// the label of each reviewed pair and whether unlisted matches are negative
+fn read_labels(labels: &RecordBatch) -> Result<(Labels, bool), AnimeError> {
+    let ids = read_match_ids(labels)?;
+    let Some(col) = labels.column_by_name("label") else {
+        return Ok((ids.into_iter().map(|pair| (pair, true)).collect(), true));
+    };
+    let col = col.as_boolean_opt().ok_or_else(|| {
+        AnimeError::InvalidMatchTable("`label` must contain booleans".to_string())
+    })?;
+    let res = ids
+        .into_iter()
+        .enumerate()
+        .filter(|(k, _)| col.is_valid(*k))
+        .map(|(k, pair)| (pair, col.value(k)))
+        .collect();
+    Ok((res, false))
+}
+
+/// Parameters controlling the growth of a [`DecisionTree`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TreeParams {
+    /// The maximum depth of the tree
+    pub max_depth: usize,
+    /// The minimum number of examples required to split a node
+    pub min_samples_split: usize,
+    /// The minimum number of examples in each leaf
+    pub min_samples_leaf: usize,
+}
+
+impl Default for TreeParams {
+    fn default() -> Self {
+        Self {
+            max_depth: 5,
+            min_samples_split: 10,
+            min_samples_leaf: 5,
+        }
+    }
+}
+
+/// A node of a [`DecisionTree`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeNode {
+    /// The proportion of positive training examples in the leaf
+    Leaf { probability: f64 },
+    /// Examples with `feature <= threshold` go left
+    Split {
+        feature: usize,
+        threshold: f64,
+        left: Box<TreeNode>,
+        right: Box<TreeNode>,
+    },
+}
+
+/// A binary classification tree over [`PairFeatures`]
+///
+/// Grown greedily by choosing the split that most reduces the
+/// Gini impurity, as in CART.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecisionTree {
+    pub root: TreeNode,
+}
+
+impl DecisionTree {
+    /// Grow a tree from features `x` and labels `y`
+    pub fn fit(x: &[PairFeatures], y: &[bool], params: &TreeParams) -> Self {
+        let idx = (0..x.len().min(y.len())).collect::<Vec<_>>();
+        Self {
+            root: grow(x, y, idx, 0, params),
+        }
+    }
+
+    /// The probability that a pair with `features` is a match
+    pub fn predict(&self, features: &PairFeatures) -> f64 {
+        let mut node = &self.root;
+        loop {
+            match node {
+                TreeNode::Leaf { probability } => return *probability,
+                TreeNode::Split {
+                    feature,
+                    threshold,
+                    left,
+                    right,
+                } => {
+                    node = if features[*feature] <= *threshold {
+                        left
+                    } else {
+                        right
+                    };
+                }
+            }
+        }
+    }
+}
+
+fn gini(n_pos: usize, n: usize) -> f64 {
+    if n == 0 {
+        return 0.0;
+    }
+    let p = n_pos as f64 / n as f64;
+    2.0 * p * (1.0 - p)
+}
+
+fn grow(
+    x: &[PairFeatures],
+    y: &[bool],
+    idx: Vec<usize>,
+    depth: usize,
+    params: &TreeParams,
+) -> TreeNode {
+    let n = idx.len();
+    let n_pos = idx.iter().filter(|i| y[**i]).count();
+    let leaf = TreeNode::Leaf {
+        probability: if n == 0 { 0.0 } else { n_pos as f64 / n as f64 },
+    };
+
+    if depth >= params.max_depth || n < params.min_samples_split || n_pos == 0 || n_pos == n {
+        return leaf;
+    }
+
+    // (weighted impurity, feature, threshold)
+    let mut best: Option<(f64, usize, f64)> = None;
+    let min_leaf = params.min_samples_leaf.max(1);
+
+    for (feature, _) in FEATURE_NAMES.iter().enumerate() {
+        let mut sorted = idx.clone();
+        sorted.sort_by(|a, b| x[*a][feature].total_cmp(&x[*b][feature]));
+
+        let mut left_pos = 0;
+        for k in 1..n {
+            if y[sorted[k - 1]] {
+                left_pos += 1;
+            }
+            let lo = x[sorted[k - 1]][feature];
+            let hi = x[sorted[k]][feature];
+            if lo == hi || k < min_leaf || n - k < min_leaf {
+                continue;
+            }
+
+            let impurity = (k as f64 * gini(left_pos, k)
+                + (n - k) as f64 * gini(n_pos - left_pos, n - k))
+                / n as f64;
+            if best.is_none_or(|(b, _, _)| impurity < b) {
+                best = Some((impurity, feature, (lo + hi) / 2.0));
+            }
+        }
+    }
+
+    match best {
+        Some((impurity, feature, threshold)) if impurity < gini(n_pos, n) => {
+            let (left, right): (Vec<_>, Vec<_>) =
+                idx.into_iter().partition(|i| x[*i][feature] <= threshold);
+            TreeNode::Split {
+                feature,
+                threshold,
+                left: Box::new(grow(x, y, left, depth + 1, params)),
+                right: Box::new(grow(x, y, right, depth + 1, params)),
+            }
+        }
+        _ => leaf,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{BooleanArray, Int32Array};
+    use geo_types::{coord, LineString};
+
+    fn features(offset: f64) -> PairFeatures {
+        [10.0, 1.0, 1.0, offset, 0.0, 1.0]
+    }
+
+    #[test]
+    fn test_decision_tree_splits_on_offset() {
+        let x = (0..20)
+            .map(|i| features(i as f64 / 10.0))
+            .collect::<Vec<_>>();
+        let y = (0..20).map(|i| i < 8).collect::<Vec<_>>();
+        let params = TreeParams {
+            max_depth: 3,
+            min_samples_split: 2,
+            min_samples_leaf: 1,
+        };
+        let tree = DecisionTree::fit(&x, &y, &params);
+
+        match &tree.root {
+            TreeNode::Split {
+                feature, threshold, ..
+            } => {
+                assert_eq!(*feature, 3);
+                assert!((threshold - 0.75).abs() < 1e-12);
+            }
+            _ => panic!("expected a split"),
+        }
+        assert_eq!(tree.predict(&features(0.1)), 1.0);
+        assert_eq!(tree.predict(&features(1.5)), 0.0);
+    }
+
+    #[test]
+    fn test_decision_tree_pure_leaf() {
+        let x = vec![features(0.0), features(1.0)];
+        let tree = DecisionTree::fit(&x, &[true, true], &TreeParams::default());
+        assert_eq!(tree.root, TreeNode::Leaf { probability: 1.0 });
+    }
+
+    #[test]
+    fn test_train_and_filter_matches() {
+        // each source is near its own target and near the other's
+        let source = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 1.6}, coord! {x: 10.0, y: 1.6}]),
+        ];
+        let target = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.1}, coord! {x: 10.0, y: 0.1}]),
+            LineString::new(vec![coord! {x: 0.0, y: 1.5}, coord! {x: 10.0, y: 1.5}]),
+        ];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 2.0, 5.0);
+        assert_eq!(anime.get_matches().unwrap().num_rows(), 4);
+
+        let schema = Schema::new(vec![
+            Field::new("source_id", DataType::Int32, false),
+            Field::new("target_id", DataType::Int32, false),
+        ]);
+        let labels = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(vec![0, 1])),
+                Arc::new(Int32Array::from(vec![0, 1])),
+            ],
+        )
+        .unwrap();
+
+        let params = TreeParams {
+            max_depth: 2,
+            min_samples_split: 2,
+            min_samples_leaf: 1,
+        };
+        let model = anime.train_acceptance(&labels, &params).unwrap();
+
+        let accepted = anime.accepted_matches(&model, 0.5).unwrap();
+        assert_eq!(accepted[&0].len(), 1);
+        assert_eq!(accepted[&0][0].source_index, 0);
+        assert_eq!(accepted[&1][0].source_index, 1);
+
+        let scored = anime.get_matches_with_acceptance(&model).unwrap();
+        assert_eq!(
+            scored.num_columns(),
+            anime.get_matches().unwrap().num_columns() + 1
+        );
+
+        let feats = anime.get_match_features().unwrap();
+        assert_eq!(feats.num_columns(), 2 + FEATURE_NAMES.len());
+    }
+
+    #[test]
+    fn test_train_on_labelled_pairs() {
+        let source = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 1.6}, coord! {x: 10.0, y: 1.6}]),
+        ];
+        let target = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.1}, coord! {x: 10.0, y: 0.1}]),
+            LineString::new(vec![coord! {x: 0.0, y: 1.5}, coord! {x: 10.0, y: 1.5}]),
+        ];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 2.0, 5.0);
+
+        // only target 0 was reviewed so target 1's matches are not examples
+        let schema = Schema::new(vec![
+            Field::new("source_id", DataType::Int32, false),
+            Field::new("target_id", DataType::Int32, false),
+            Field::new("label", DataType::Boolean, true),
+        ]);
+        let labels = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(vec![0, 1, 1])),
+                Arc::new(Int32Array::from(vec![0, 0, 1])),
+                Arc::new(BooleanArray::from(vec![Some(true), Some(false), None])),
+            ],
+        )
+        .unwrap();
+
+        let params = TreeParams {
+            max_depth: 2,
+            min_samples_split: 2,
+            min_samples_leaf: 1,
+        };
+        let model = anime.train_acceptance(&labels, &params).unwrap();
+        let accepted = anime.accepted_matches(&model, 0.5).unwrap();
+        assert_eq!(accepted[&0].len(), 1);
+        assert_eq!(accepted[&0][0].source_index, 0);
+
+        // a single positive and negative example split into pure leaves
+        let TreeNode::Split { left, right, .. } = &model.root else {
+            panic!("expected a split");
+        };
+        assert_eq!(**left, TreeNode::Leaf { probability: 1.0 });
+        assert_eq!(**right, TreeNode::Leaf { probability: 0.0 });
+    }
+
+    #[test]
+    fn test_train_invalid_label() {
+        let anime = Anime::new_with_segments(
+            vec![LineString::new(vec![
+                coord! {x: 0.0, y: 0.0},
+                coord! {x: 10.0, y: 0.0},
+            ])]
+            .into_iter(),
+            vec![LineString::new(vec![
+                coord! {x: 0.0, y: 0.1},
+                coord! {x: 10.0, y: 0.1},
+            ])]
+            .into_iter(),
+            1.0,
+            5.0,
+        );
+        let schema = Schema::new(vec![
+            Field::new("source_id", DataType::Int32, false),
+            Field::new("target_id", DataType::Int32, false),
+            Field::new("label", DataType::Int32, false),
+        ]);
+        let labels = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(vec![0])),
+                Arc::new(Int32Array::from(vec![0])),
+                Arc::new(Int32Array::from(vec![1])),
+            ],
+        )
+        .unwrap();
+        let res = anime.train_acceptance(&labels, &TreeParams::default());
+        assert!(matches!(res, Err(AnimeError::InvalidMatchTable(_))));
+    }
+
+    #[test]
+    fn test_accepted_matches_requires_segments() {
+        let anime = Anime::new(
+            vec![LineString::new(vec![
+                coord! {x: 0.0, y: 0.0},
+                coord! {x: 10.0, y: 0.0},
+            ])]
+            .into_iter(),
+            vec![LineString::new(vec![
+                coord! {x: 0.0, y: 0.1},
+                coord! {x: 10.0, y: 0.1},
+            ])]
+            .into_iter(),
+            1.0,
+            5.0,
+        );
+        let model = DecisionTree::fit(&[features(0.0)], &[true], &TreeParams::default());
+        let res = anime.accepted_matches(&model, 0.5);
+        assert!(matches!(res, Err(AnimeError::SegmentsNotRecorded)));
+    }
+}
@@ -1,13 +1,37 @@
+pub mod assign;
+pub mod confidence;
+pub mod coverage;
+pub mod diff;
+pub mod direction;
+pub mod evaluate;
+pub mod flow;
 pub mod get_matches;
+pub mod incremental;
+pub mod index;
 pub mod interpolate;
+pub mod learn;
+pub mod metrics;
+pub mod nodes;
 mod overlap;
+pub mod quality;
+pub mod self_match;
 pub mod structs;
-
+pub mod suggest;
+pub mod sweep;
+pub mod synthetic;
+pub mod tiled;
+pub mod tolerance;
+pub mod topology;
+pub mod validate;
+
+use crate::confidence::ConfidenceModel;
+use crate::direction::{component_slopes, DirectionSmoothing};
+use crate::tolerance::FeatureTolerances;
 use crate::{
     overlap::*, overlap_range, solve_no_x_overlap, solve_no_y_overlap, structs::*, x_range,
     y_range, TarLine,
 };
-use geo::{BoundingRect, Distance, Euclidean, Length};
+use geo::{BoundingRect, Densify, Distance, Euclidean, Length, Simplify};
 use rstar::primitives::{CachedEnvelope, GeomWithData};
 use std::{cell::OnceCell, collections::BTreeMap, error::Error, fmt::Display};
 
@@ -18,6 +42,27 @@ pub enum AnimeError {
     MatchesNotFound,
     AlreadyMatched(MatchesMap),
     ContainsNull,
+    NoCandidatesFound,
+    IncorrectToleranceLength,
+    InvalidMatchTable(String),
+    InvalidGeometry {
+        side: Side,
+        index: usize,
+        reason: &'static str,
+    },
+    FeatureNotFound {
+        side: Side,
+        index: usize,
+    },
+    Io(String),
+    SegmentsNotRecorded,
+    InvalidTileSize(f64),
+    FeatureTolerancesUnsupported,
+    InvalidKernel(&'static str),
+    InvalidLoadOption {
+        option: &'static str,
+        value: f64,
+    },
 }
 
 impl Display for AnimeError {
@@ -27,17 +72,51 @@ impl Display for AnimeError {
             AnimeError::MatchesNotFound => write!(f, "`matches` needs to be instantiated with `self.find_matches()`"),
             AnimeError::AlreadyMatched(_) => write!(f, "matches already found."),
             AnimeError::ContainsNull => write!(f, "cannot interpolate null values"),
+            AnimeError::IncorrectToleranceLength => write!(f, "Per-feature tolerances must have the same number of observations as their `source` or `target` lines"),
+            AnimeError::NoCandidatesFound => write!(f, "no near-parallel target segments found within the search distance"),
+            AnimeError::InvalidMatchTable(reason) => write!(f, "invalid match table: {reason}"),
+            AnimeError::InvalidGeometry { side, index, reason } => write!(f, "invalid {side} geometry at index {index}: {reason}"),
+            AnimeError::FeatureNotFound { side, index } => write!(f, "no {side} geometry at index {index}"),
+            AnimeError::Io(reason) => write!(f, "io error: {reason}"),
+            AnimeError::InvalidTileSize(size) => write!(f, "`tile_size` must be finite and positive, found {size}"),
+            AnimeError::FeatureTolerancesUnsupported => write!(f, "per-feature tolerances are not supported by this operation"),
+            AnimeError::InvalidKernel(reason) => write!(f, "invalid decay kernel: {reason}"),
+            AnimeError::InvalidLoadOption { option, value } => write!(f, "invalid `{option}` in `LoadOptions`: {value}"),
+            AnimeError::SegmentsNotRecorded => write!(f, "matched component lines are only recorded when `record_segments` is set before `self.find_matches()`"),
         }
     }
 }
 
 impl Error for AnimeError {}
 
+/// The input a geometry belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Source,
+    Target,
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::Source => write!(f, "source"),
+            Side::Target => write!(f, "target"),
+        }
+    }
+}
+
+/// Data stored with each component line in the R* Trees
+///
+/// The tuple contains the index of the `LineString`, the slope used
+/// for angle comparisons, and the position of the component line
+/// within its `LineString`.
+pub type ComponentData = (usize, f64, usize);
+
 /// R* Tree for source geometries
-pub type SourceTree = rstar::RTree<GeomWithData<CachedEnvelope<geo_types::Line>, (usize, f64)>>;
+pub type SourceTree = rstar::RTree<GeomWithData<CachedEnvelope<geo_types::Line>, ComponentData>>;
 
 /// R* Tree for target geometries
-pub type TargetTree = rstar::RTree<GeomWithData<CachedEnvelope<TarLine>, (usize, f64)>>;
+pub type TargetTree = rstar::RTree<GeomWithData<CachedEnvelope<TarLine>, ComponentData>>;
 
 /// Represents a partial source <-> target match
 #[derive(Debug, Clone)]
@@ -46,16 +125,127 @@ pub struct MatchCandidate {
     pub source_index: usize,
     /// The amount of shared length between two geometries
     pub shared_len: f64,
+    /// The amount of shared length where both geometries are digitised
+    /// in the same direction
+    pub aligned_len: f64,
+    /// The component line pairs that share length
+    ///
+    /// Only recorded when [`Anime::record_segments`] is set, otherwise empty.
+    /// The segments are never rescaled so their lengths sum to `shared_len`
+    /// only for the matches found by [`Anime::find_matches`], not those
+    /// down-weighted by [`Anime::disambiguate`] or allocated by
+    /// [`Anime::optimal_matches`].
+    pub segments: Vec<SegmentMatch>,
+}
+
+/// Represents a pair of matched source and target component lines
+///
+/// `source` is the portion of the source component line that is shared,
+/// oriented in the source's direction. `target` is that portion projected
+/// onto the target component line, oriented in the target's direction.
+#[derive(Debug, Clone)]
+pub struct SegmentMatch {
+    /// The position of the source component line within its `LineString`
+    pub source_component: usize,
+    /// The position of the target component line within its `LineString`
+    pub target_component: usize,
+    /// The shared portion of the source component line
+    pub source: geo_types::Line,
+    /// The shared portion projected onto the target component line
+    pub target: geo_types::Line,
+    /// The minimum distance between the two component lines
+    pub distance: f64,
+    /// The angle difference, in degrees, used when matching
+    pub angle_diff: f64,
+}
+
+impl SegmentMatch {
+    /// The length of the shared portion of the source component line
+    pub fn shared_len(&self) -> f64 {
+        self.source.length::<Euclidean>()
+    }
+
+    /// The lateral offset at the start and end of the shared portion
+    pub fn offsets(&self) -> (f64, f64) {
+        let d1 = Euclidean::distance(self.source.start_point(), self.target.start_point());
+        let d2 = Euclidean::distance(self.source.end_point(), self.target.end_point());
+        (d1, d2)
+    }
+
+    /// The mean lateral offset of the shared portion
+    pub fn offset(&self) -> f64 {
+        let (d1, d2) = self.offsets();
+        (d1 + d2) / 2.0
+    }
 }
 
 /// Stores match length
 ///
 /// The BTreeMap key is the index of the target geometry
-/// whereas the entry contains
+/// whereas the entry contains the sources matched to it
 pub type TargetIndex = usize;
 pub type MatchesMap = BTreeMap<TargetIndex, Vec<MatchCandidate>>;
 pub type Matches = OnceCell<MatchesMap>;
 
+/// Options applied when loading geometries
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Per-feature distance and angle tolerances
+    pub feature_tolerances: Option<FeatureTolerances>,
+    /// Smoothing of the directions used for angle comparisons
+    pub smoothing: Option<DirectionSmoothing>,
+    /// Simplify each `LineString` with the Ramer-Douglas-Peucker algorithm
+    /// using this tolerance before it is indexed
+    pub simplify_tolerance: Option<f64>,
+    /// Densify each `LineString` so that no component line is longer
+    /// than this before it is indexed
+    pub max_segment_len: Option<f64>,
+    /// Return [`AnimeError::InvalidGeometry`] for the first empty,
+    /// degenerate, or non-finite `LineString` instead of leaving it unmatched
+    pub strict: bool,
+}
+
+impl LoadOptions {
+    /// Check the simplification and densification parameters
+    ///
+    /// `max_segment_len` must be finite and positive and
+    /// `simplify_tolerance` must be finite and non-negative.
+    pub fn validate(&self) -> Result<(), AnimeError> {
+        if let Some(max_len) = self.max_segment_len {
+            if !max_len.is_finite() || max_len <= 0.0 {
+                return Err(AnimeError::InvalidLoadOption {
+                    option: "max_segment_len",
+                    value: max_len,
+                });
+            }
+        }
+        if let Some(epsilon) = self.simplify_tolerance {
+            if !epsilon.is_finite() || epsilon < 0.0 {
+                return Err(AnimeError::InvalidLoadOption {
+                    option: "simplify_tolerance",
+                    value: epsilon,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Simplify then densify a `LineString` before it is indexed
+    ///
+    /// Feature lengths are always measured before preparation so
+    /// that the weights are based on the original geometry.
+    fn prepare(&self, x: geo_types::LineString) -> geo_types::LineString {
+        let x = match self.simplify_tolerance {
+            Some(epsilon) => x.simplify(&epsilon),
+            None => x,
+        };
+        match self.max_segment_len {
+            Some(max_len) => x.densify::<Euclidean>(max_len),
+            None => x,
+        }
+    }
+}
+
 /// Approximate Network Matching, Integration, and Enrichment
 ///
 /// This struct contains all of the information needed to perform
@@ -68,10 +258,35 @@ pub type Matches = OnceCell<MatchesMap>;
 ///
 /// The lengths, represented as `Vec<f64>` are required for the
 /// integration of attributes.
+///
+/// When `feature_tolerances` is set, the per-feature tolerances take
+/// precedence over `distance_tolerance` and `angle_tolerance`.
+///
+/// When `directed` is `true`, the `angle_tolerance` is compared against
+/// the difference in bearings (0–360°) rather than undirected slopes so
+/// lines digitised in opposite directions do not match. It must be set
+/// before matches are found.
+///
+/// When `record_segments` is `true`, the matched component line pairs
+/// are kept in [`MatchCandidate::segments`]. They are required by the
+/// similarity metrics, coverage, diff, and learned acceptance, and by
+/// distance-decay weights. It must be set before matches are found.
+///
+/// The `load_options` are those the geometries were loaded with and are
+/// reused for geometries added with [`Anime::insert_source`] and
+/// [`Anime::insert_target`]. Their `feature_tolerances` are moved to
+/// `feature_tolerances`.
+///
+/// The `confidence_model` scores each match. See [`ConfidenceModel`].
 #[derive(Clone, Debug)]
 pub struct Anime {
     pub distance_tolerance: f64,
     pub angle_tolerance: f64,
+    pub feature_tolerances: Option<FeatureTolerances>,
+    pub directed: bool,
+    pub record_segments: bool,
+    pub load_options: LoadOptions,
+    pub confidence_model: ConfidenceModel,
     pub source_tree: SourceTree,
     pub source_lens: Vec<f64>,
     pub target_tree: TargetTree,
@@ -93,11 +308,18 @@ impl Anime {
     ) -> Self {
         let mut source_lens = Vec::new();
         let mut target_lens = Vec::new();
-        let source_tree = create_source_rtree(source, &mut source_lens);
-        let target_tree = create_target_rtree(target, &mut target_lens, distance_tolerance);
+        let options = LoadOptions::default();
+        let source_tree = create_source_rtree(source, &mut source_lens, &options);
+        let target_tree =
+            create_target_rtree(target, &mut target_lens, |_| distance_tolerance, &options);
         Self {
             distance_tolerance,
             angle_tolerance,
+            feature_tolerances: None,
+            directed: false,
+            record_segments: false,
+            load_options: LoadOptions::default(),
+            confidence_model: ConfidenceModel::default(),
             source_tree,
             source_lens,
             target_tree,
@@ -106,22 +328,134 @@ impl Anime {
         }
     }
 
+    /// Load source and target `LineString` geometries with per-feature tolerances
+    ///
+    /// Each target's component lines are padded by the largest distance
+    /// tolerance it can have with any source. `distance_tolerance` and
+    /// `angle_tolerance` are used when neither side has a per-feature value.
+    /// See [`FeatureTolerances`] for how the tolerances of a pair are resolved.
+    ///
+    /// Matches must be found with [`Anime::find_matches`].
+    pub fn load_geometries_with_tolerances(
+        source: impl Iterator<Item = geo_types::LineString>,
+        target: impl Iterator<Item = geo_types::LineString>,
+        distance_tolerance: f64,
+        angle_tolerance: f64,
+        feature_tolerances: FeatureTolerances,
+    ) -> Result<Self, AnimeError> {
+        let options = LoadOptions {
+            feature_tolerances: Some(feature_tolerances),
+            ..Default::default()
+        };
+        Self::load_geometries_with_options(
+            source,
+            target,
+            distance_tolerance,
+            angle_tolerance,
+            options,
+        )
+    }
+
+    /// Load source and target `LineString` geometries with [`LoadOptions`]
+    ///
+    /// Matches must be found with [`Anime::find_matches`]. The options are
+    /// checked with [`LoadOptions::validate`] before any geometry is read.
+    pub fn load_geometries_with_options(
+        source: impl Iterator<Item = geo_types::LineString>,
+        target: impl Iterator<Item = geo_types::LineString>,
+        distance_tolerance: f64,
+        angle_tolerance: f64,
+        options: LoadOptions,
+    ) -> Result<Self, AnimeError> {
+        options.validate()?;
+        let mut invalid = None;
+        let mut source_lens = Vec::new();
+        let mut target_lens = Vec::new();
+
+        let source = check_geometries(Side::Source, source, options.strict, &mut invalid);
+        let source_tree = create_source_rtree(source, &mut source_lens, &options);
+        if let Some(err) = invalid.take() {
+            return Err(err);
+        }
+        // the number of targets is only known once they are streamed
+        if let Some(ft) = &options.feature_tolerances {
+            if !ft.is_valid_source(source_lens.len()) {
+                return Err(AnimeError::IncorrectToleranceLength);
+            }
+        }
+
+        let target = check_geometries(Side::Target, target, options.strict, &mut invalid);
+        let target_tree = match &options.feature_tolerances {
+            Some(ft) => create_target_rtree(
+                target,
+                &mut target_lens,
+                ft.target_padding(distance_tolerance),
+                &options,
+            ),
+            None => create_target_rtree(target, &mut target_lens, |_| distance_tolerance, &options),
+        };
+        if let Some(err) = invalid.take() {
+            return Err(err);
+        }
+
+        if let Some(ft) = &options.feature_tolerances {
+            if !ft.is_valid_target(target_lens.len()) {
+                return Err(AnimeError::IncorrectToleranceLength);
+            }
+        }
+
+        let mut load_options = options;
+        Ok(Self {
+            distance_tolerance,
+            angle_tolerance,
+            feature_tolerances: load_options.feature_tolerances.take(),
+            directed: false,
+            record_segments: false,
+            load_options,
+            confidence_model: ConfidenceModel::default(),
+            source_tree,
+            source_lens,
+            target_tree,
+            target_lens,
+            matches: OnceCell::new(),
+        })
+    }
+
     /// Find candidate matches between source and target
     ///
     /// The matches can only be found once for each source and target pair.
+    /// Returns [`AnimeError::IncorrectToleranceLength`] when the per-feature
+    /// tolerances do not have one value per geometry.
     pub fn find_matches(&mut self) -> Result<&mut Anime, AnimeError> {
+        if let Some(ft) = &self.feature_tolerances {
+            if !ft.is_valid(self.source_lens.len(), self.target_lens.len()) {
+                return Err(AnimeError::IncorrectToleranceLength);
+            }
+        }
         let matches = find_candidate_matches(
             &self.source_tree,
             &self.target_tree,
             self.angle_tolerance,
             self.distance_tolerance,
+            self.feature_tolerances.as_ref(),
+            self.directed,
+            self.record_segments,
         );
         self.matches
             .set(matches)
-            .map_err(|e| AnimeError::AlreadyMatched(e))?;
+            .map_err(AnimeError::AlreadyMatched)?;
         Ok(self)
     }
 
+    /// Check that the matched component lines were recorded
+    pub(crate) fn require_segments(&self) -> Result<(), AnimeError> {
+        if self.record_segments {
+            Ok(())
+        } else {
+            Err(AnimeError::SegmentsNotRecorded)
+        }
+    }
+
     /// Insert linestring geometries and find matches
     pub fn new(
         source: impl Iterator<Item = geo_types::LineString>,
@@ -131,17 +465,27 @@ impl Anime {
     ) -> Self {
         let mut source_lens = Vec::new();
         let mut target_lens = Vec::new();
-        let source_tree = create_source_rtree(source, &mut source_lens);
-        let target_tree = create_target_rtree(target, &mut target_lens, distance_tolerance);
+        let options = LoadOptions::default();
+        let source_tree = create_source_rtree(source, &mut source_lens, &options);
+        let target_tree =
+            create_target_rtree(target, &mut target_lens, |_| distance_tolerance, &options);
         let matches = find_candidate_matches(
             &source_tree,
             &target_tree,
             angle_tolerance,
             distance_tolerance,
+            None,
+            false,
+            false,
         );
         Self {
             distance_tolerance,
             angle_tolerance,
+            feature_tolerances: None,
+            directed: false,
+            record_segments: false,
+            load_options: LoadOptions::default(),
+            confidence_model: ConfidenceModel::default(),
             source_tree,
             source_lens,
             target_tree,
@@ -149,100 +493,359 @@ impl Anime {
             matches: OnceCell::from(matches),
         }
     }
+
+    /// As [`Anime::new`] but recording the matched component lines
+    #[cfg(test)]
+    pub(crate) fn new_with_segments(
+        source: impl Iterator<Item = geo_types::LineString>,
+        target: impl Iterator<Item = geo_types::LineString>,
+        distance_tolerance: f64,
+        angle_tolerance: f64,
+    ) -> Self {
+        let mut anime = Self::load_geometries(source, target, distance_tolerance, angle_tolerance);
+        anime.record_segments = true;
+        anime.find_matches().expect("matches are not yet found");
+        anime
+    }
 }
 fn find_candidate_matches(
     source_tree: &SourceTree,
     target_tree: &TargetTree,
     angle_tolerance: f64,
     distance_tolerance: f64,
+    feature_tolerances: Option<&FeatureTolerances>,
+    directed: bool,
+    record_segments: bool,
 ) -> MatchesMap {
     let mut matches: MatchesMap = BTreeMap::new();
     let candidates = source_tree.intersection_candidates_with_other_tree(target_tree);
 
     candidates.for_each(|(cx, cy)| {
-        let xbb = cx.geom().bounding_rect();
-        let ybb = cy.geom().0.bounding_rect();
-
-        // extract cached slopes and index positions
-        let (i, x_slope) = cx.data;
-        let (j, y_slope) = cy.data;
-
-        // convert calculated slopes to degrees
-        let x_deg = x_slope.atan().to_degrees();
-        let y_deg = y_slope.atan().to_degrees();
-
-        // compare slopes:
-        let is_tolerant = (x_deg - y_deg).abs() < angle_tolerance;
-
-        // if the slopes are within tolerance then we check for overlap
-        if is_tolerant {
-            let xx_range = x_range(&xbb);
-            let xy_range = x_range(&ybb);
-            let x_overlap = overlap_range(xx_range, xy_range);
-            let y_overlap = overlap_range(y_range(&xbb), y_range(&ybb));
-
-            // if theres overlap then we do a distance based check
-            // following, check that they're within distance tolerance,
-            // if so, calculate the shared length
-            if x_overlap.is_some() || y_overlap.is_some() {
-                // calculate the distance from the line segment
-                // if its within our threshold we include it;
-                let d = cy.geom().distance(cx.geom());
-
-                // if distance is less than or equal to tolerance, add the key
-                if d <= distance_tolerance {
-                    let shared_len = if x_slope.atan().to_degrees() <= 45.0 {
-                        if x_overlap.is_some() {
-                            let (p1, p2) =
-                                solve_no_y_overlap(x_overlap.unwrap(), cx.geom(), &x_slope);
-
-                            Euclidean::distance(&p1, &p2)
-                        } else {
-                            0.0
-                        }
-                    } else if y_overlap.is_some() {
-                        let (p1, p2) = solve_no_x_overlap(y_overlap.unwrap(), cx.geom(), &x_slope);
-                        Euclidean::distance(&p1, &p2)
-                    } else {
-                        0.0
-                    };
-                    // add 1 for R indexing
-                    // ensures that no duplicates are inserted. Creates a new empty vector is needed
-                    let entry = matches.entry(j).or_default();
-
-                    if let Some(tuple) = entry.iter_mut().find(|x| x.source_index == i) {
-                        tuple.shared_len += shared_len;
-                    } else {
-                        entry.push(MatchCandidate {
-                            source_index: i,
-                            shared_len,
-                        });
-                    }
-                }
-            }
+        let (i, j) = (cx.data.0, cy.data.0);
+
+        // resolve the tolerances for this pair
+        let (angle_tolerance, distance_tolerance) = match feature_tolerances {
+            Some(ft) => (
+                ft.angle(i, j, angle_tolerance),
+                ft.distance(i, j, distance_tolerance),
+            ),
+            None => (angle_tolerance, distance_tolerance),
+        };
+
+        let (x, y) = (cx.geom(), &cy.geom().0);
+        if let Some((cmp, segment)) = compare_components(
+            x,
+            cx.data,
+            y,
+            cy.data,
+            angle_tolerance,
+            distance_tolerance,
+            directed,
+        ) {
+            let segment = segment.filter(|_| record_segments);
+            insert_match(&mut matches, i, j, &cmp, segment);
         }
     });
     matches
 }
 
+/// Add shared length to the match between source `i` and target `j`
+///
+/// Ensures that no duplicates are inserted. Creates a new empty vector if needed.
+fn insert_match(
+    matches: &mut MatchesMap,
+    i: usize,
+    j: usize,
+    cmp: &SegmentComparison,
+    segment: Option<SegmentMatch>,
+) {
+    let entry = matches.entry(j).or_default();
+    let aligned_len = if cmp.aligned { cmp.shared_len } else { 0.0 };
+
+    if let Some(tuple) = entry.iter_mut().find(|x| x.source_index == i) {
+        tuple.shared_len += cmp.shared_len;
+        tuple.aligned_len += aligned_len;
+        tuple.segments.extend(segment);
+    } else {
+        entry.push(MatchCandidate {
+            source_index: i,
+            shared_len: cmp.shared_len,
+            aligned_len,
+            segments: segment.into_iter().collect(),
+        });
+    }
+}
+
+/// Project the end points of a line onto a target line
+///
+/// The result is oriented in the direction of the target line.
+fn project_line(y: &geo_types::Line, x: &geo_types::Line) -> geo_types::Line {
+    let delta = y.delta();
+    let len_2 = delta.x * delta.x + delta.y * delta.y;
+    let project = |c: geo_types::Coord| {
+        if len_2 == 0.0 {
+            return y.start;
+        }
+        let t = ((c.x - y.start.x) * delta.x + (c.y - y.start.y) * delta.y) / len_2;
+        y.start + delta * t.clamp(0.0, 1.0)
+    };
+    let projected = geo_types::Line::new(project(x.start), project(x.end));
+    let pd = projected.delta();
+    if pd.x * delta.x + pd.y * delta.y < 0.0 {
+        geo_types::Line::new(projected.end, projected.start)
+    } else {
+        projected
+    }
+}
+
+/// Compare a source and target component line
+///
+/// Returns the comparison and the matched segment when the lines are
+/// within both tolerances.
+fn compare_components(
+    x: &geo_types::Line,
+    x_data: ComponentData,
+    y: &geo_types::Line,
+    y_data: ComponentData,
+    angle_tolerance: f64,
+    distance_tolerance: f64,
+    directed: bool,
+) -> Option<(SegmentComparison, Option<SegmentMatch>)> {
+    // extract cached slopes and index positions
+    let (_, x_slope, k) = x_data;
+    let (_, y_slope, l) = y_data;
+
+    // compare slopes, or bearings when directed:
+    // NaN angles are never tolerant
+    let pair_angle = angle_diff(x, x_slope, y, y_slope, directed);
+    let is_tolerant = pair_angle < angle_tolerance;
+    if !is_tolerant {
+        return None;
+    }
+
+    // if the slopes are within tolerance then we check for overlap
+    // and distance. If both pass, the shared length is recorded
+    let cmp = compare_segments(x, y)?;
+    if cmp.distance > distance_tolerance {
+        return None;
+    }
+    let segment = cmp.overlap.map(|overlap| SegmentMatch {
+        source_component: k,
+        target_component: l,
+        source: overlap,
+        target: project_line(y, &overlap),
+        distance: cmp.distance,
+        angle_diff: pair_angle,
+    });
+    Some((cmp, segment))
+}
+
+/// Angle difference, in degrees, between a source and target line
+///
+/// Undirected comparisons use the slopes whereas directed comparisons
+/// use the bearings of the lines.
+fn angle_diff(
+    x: &geo_types::Line,
+    x_slope: f64,
+    y: &geo_types::Line,
+    y_slope: f64,
+    directed: bool,
+) -> f64 {
+    if directed {
+        bearing_diff(oriented_bearing(x, x_slope), oriented_bearing(y, y_slope))
+    } else {
+        slope_angle_diff(x_slope, y_slope)
+    }
+}
+
+/// Bearing of a line in degrees clockwise from the positive y axis (0–360°)
+pub(crate) fn bearing(x: &geo_types::Line) -> f64 {
+    let delta = x.delta();
+    delta.x.atan2(delta.y).to_degrees().rem_euclid(360.0)
+}
+
+/// Bearing of a slope oriented in the direction of a line
+///
+/// Used when the slope has been smoothed and may differ from the line's own.
+fn oriented_bearing(x: &geo_types::Line, slope: f64) -> f64 {
+    let (dx, dy) = if slope.is_infinite() {
+        (0.0, slope.signum())
+    } else {
+        (1.0, slope)
+    };
+    let delta = x.delta();
+    let sign = if dx * delta.x + dy * delta.y < 0.0 {
+        -1.0
+    } else {
+        1.0
+    };
+    (sign * dx).atan2(sign * dy).to_degrees().rem_euclid(360.0)
+}
+
+/// Smallest absolute difference between two bearings (0–180°)
+pub(crate) fn bearing_diff(a: f64, b: f64) -> f64 {
+    let diff = (a - b).abs() % 360.0;
+    diff.min(360.0 - diff)
+}
+
+/// Absolute difference, in degrees, between the angles of two slopes
+fn slope_angle_diff(x_slope: f64, y_slope: f64) -> f64 {
+    // convert calculated slopes to degrees
+    let x_deg = x_slope.atan().to_degrees();
+    let y_deg = y_slope.atan().to_degrees();
+    (x_deg - y_deg).abs()
+}
+
+/// The geometric relationship between a source and a target component line
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SegmentComparison {
+    /// The minimum euclidean distance between the two lines
+    pub distance: f64,
+    /// The length of the source line projected onto the overlapping range
+    pub shared_len: f64,
+    /// The shared portion of the source line oriented in its direction
+    pub overlap: Option<geo_types::Line>,
+    /// Whether the lines' bearings differ by less than 90 degrees
+    pub aligned: bool,
+}
+
+/// Compare a source line with a target line
+///
+/// Returns `None` when the bounding boxes of the two lines do not
+/// overlap in either the x or y direction.
+fn compare_segments(x: &geo_types::Line, y: &geo_types::Line) -> Option<SegmentComparison> {
+    // the cached slope may be smoothed so the line's own slope is used
+    let x_slope = x.slope();
+    let xbb = x.bounding_rect();
+    let ybb = y.bounding_rect();
+
+    let x_overlap = overlap_range(x_range(&xbb), x_range(&ybb));
+    let y_overlap = overlap_range(y_range(&xbb), y_range(&ybb));
+
+    // if theres no overlap then there is nothing to share
+    if x_overlap.is_none() && y_overlap.is_none() {
+        return None;
+    }
+
+    // calculate the distance from the line segment
+    let distance = Euclidean::distance(y, x);
+
+    let overlap = if x_slope.atan().to_degrees() <= 45.0 {
+        x_overlap.map(|x_overlap| solve_no_y_overlap(x_overlap, x, &x_slope))
+    } else {
+        y_overlap.map(|y_overlap| solve_no_x_overlap(y_overlap, x, &x_slope))
+    };
+
+    // orient the shared portion in the direction of the source line
+    let overlap = overlap.map(|(p1, p2)| {
+        let portion = geo_types::Line::new(p1, p2);
+        let (pd, xd) = (portion.delta(), x.delta());
+        if pd.x * xd.x + pd.y * xd.y < 0.0 {
+            geo_types::Line::new(p2, p1)
+        } else {
+            portion
+        }
+    });
+
+    let shared_len = overlap
+        .map(|o| Euclidean::distance(o.start_point(), o.end_point()))
+        .unwrap_or(0.0);
+
+    let aligned = bearing_diff(bearing(x), bearing(y)) < 90.0;
+
+    Some(SegmentComparison {
+        distance,
+        shared_len,
+        overlap,
+        aligned,
+    })
+}
+
+/// Why a `LineString` cannot be matched
+///
+/// Returns `None` for `LineString`s with at least two finite
+/// coordinates and a positive length.
+pub(crate) fn degenerate_reason(x: &geo_types::LineString) -> Option<&'static str> {
+    match x.0.len() {
+        0 => Some("empty"),
+        1 => Some("single vertex"),
+        _ if x.0.iter().any(|c| !(c.x.is_finite() && c.y.is_finite())) => {
+            Some("non-finite coordinates")
+        }
+        _ if x.0.windows(2).all(|w| w[0] == w[1]) => Some("zero length"),
+        _ => None,
+    }
+}
+
+/// Return the first degenerate `LineString` as an error
+///
+/// When `strict`, iteration stops at the first degenerate `LineString`
+/// and the error is stored in `invalid` so the input is never buffered.
+fn check_geometries<'a>(
+    side: Side,
+    x: impl Iterator<Item = geo_types::LineString> + 'a,
+    strict: bool,
+    invalid: &'a mut Option<AnimeError>,
+) -> impl Iterator<Item = geo_types::LineString> + 'a {
+    x.enumerate().map_while(move |(index, xi)| {
+        let reason = if strict { degenerate_reason(&xi) } else { None };
+        match reason {
+            Some(reason) => {
+                *invalid = Some(AnimeError::InvalidGeometry {
+                    side,
+                    index,
+                    reason,
+                });
+                None
+            }
+            None => Some(xi),
+        }
+    })
+}
+
+/// The length and prepared component lines of a `LineString`
+///
+/// Degenerate `LineString`s have no length or component lines so they keep
+/// their index but are never matched. Zero length component lines are
+/// dropped but the positions of the remaining lines are kept.
+fn prepare_lines(
+    x: geo_types::LineString,
+    options: &LoadOptions,
+) -> (f64, Vec<(geo_types::Line, f64, usize)>) {
+    if degenerate_reason(&x).is_some() {
+        return (0.0, Vec::new());
+    }
+    let len = x.length::<Euclidean>();
+    let x = options.prepare(x);
+    let lines = x.lines().collect::<Vec<_>>();
+    let slopes = component_slopes(&lines, options.smoothing.as_ref());
+    let components = lines
+        .into_iter()
+        .zip(slopes)
+        .enumerate()
+        .filter(|(_, (li, _))| li.start != li.end)
+        .map(|(k, (li, slope))| (li, slope, k))
+        .collect();
+    (len, components)
+}
+
 fn create_source_rtree(
     x: impl Iterator<Item = geo_types::LineString>,
     source_lens: &mut Vec<f64>,
+    options: &LoadOptions,
 ) -> SourceTree {
     let to_insert = x
         .enumerate()
         .flat_map(|(i, xi)| {
-            let xi_len = xi.length::<Euclidean>();
+            let (xi_len, components) = prepare_lines(xi, options);
             source_lens.push(xi_len);
-            let components = xi
-                .lines()
-                .map(|li| {
-                    let slope = li.slope();
+            components
+                .into_iter()
+                .map(|(li, slope, k)| {
                     let env = CachedEnvelope::new(li);
-                    GeomWithData::new(env, (i, slope))
+                    GeomWithData::new(env, (i, slope, k))
                 })
-                .collect::<Vec<GeomWithData<_, _>>>();
-            components
+                .collect::<Vec<GeomWithData<_, _>>>()
         })
         .collect::<Vec<_>>();
 
@@ -252,23 +855,39 @@ fn create_source_rtree(
 fn create_target_rtree(
     y: impl Iterator<Item = geo_types::LineString>,
     target_lens: &mut Vec<f64>,
-    dist: f64,
+    padding: impl Fn(usize) -> f64,
+    options: &LoadOptions,
 ) -> TargetTree {
     let to_insert = y
         .enumerate()
         .flat_map(|(i, yi)| {
-            let yi_len = yi.length::<Euclidean>();
+            let (yi_len, components) = prepare_lines(yi, options);
             target_lens.push(yi_len);
-            let components = yi
-                .lines()
-                .map(|li| {
+            let dist = padding(i);
+            components
+                .into_iter()
+                .map(|(li, slope, k)| {
                     let tl = TarLine(li, dist);
-                    let slope = li.slope();
                     let env = CachedEnvelope::new(tl);
-                    GeomWithData::new(env, (i, slope))
+                    GeomWithData::new(env, (i, slope, k))
                 })
-                .collect::<Vec<GeomWithData<_, _>>>();
-            components
+                .collect::<Vec<GeomWithData<_, _>>>()
+        })
+        .collect::<Vec<_>>();
+
+    rstar::RTree::bulk_load(to_insert)
+}
+
+/// Rebuild a target tree with a new padding distance
+///
+/// The component lines and their data are reused so the original
+/// `LineString`s are not required.
+fn repad_target_tree(tree: &TargetTree, dist: f64) -> TargetTree {
+    let to_insert = tree
+        .iter()
+        .map(|gi| {
+            let tl = TarLine(gi.geom().0, dist);
+            GeomWithData::new(CachedEnvelope::new(tl), gi.data)
         })
         .collect::<Vec<_>>();
 
@@ -473,6 +1092,247 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_feature_tolerances_per_target() {
+        use crate::tolerance::{CombineTolerance, FeatureTolerances};
+
+        let source = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 50.0}, coord! {x: 10.0, y: 50.0}]),
+        ];
+        // both targets are offset by 2 from their source
+        let target = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 2.0}, coord! {x: 10.0, y: 2.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 52.0}, coord! {x: 10.0, y: 52.0}]),
+        ];
+
+        let tols = FeatureTolerances {
+            target_distance: Some(vec![0.5, 3.0]),
+            combine: CombineTolerance::Max,
+            ..Default::default()
+        };
+
+        let mut anime = Anime::load_geometries_with_tolerances(
+            source.into_iter(),
+            target.into_iter(),
+            0.5,
+            5.0,
+            tols,
+        )
+        .unwrap();
+        anime.find_matches().unwrap();
+
+        let matches = anime.matches.get().unwrap();
+        assert!(!matches.contains_key(&0));
+        assert_eq!(matches.get(&1).unwrap()[0].source_index, 1);
+    }
+
+    #[test]
+    fn test_feature_tolerances_combine_min() {
+        use crate::tolerance::{CombineTolerance, FeatureTolerances};
+
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 2.0},
+            coord! {x: 10.0, y: 2.0},
+        ])];
+
+        let tols = FeatureTolerances {
+            source_distance: Some(vec![1.0]),
+            target_distance: Some(vec![3.0]),
+            combine: CombineTolerance::Min,
+            ..Default::default()
+        };
+
+        let mut anime = Anime::load_geometries_with_tolerances(
+            source.into_iter(),
+            target.into_iter(),
+            10.0,
+            5.0,
+            tols,
+        )
+        .unwrap();
+        anime.find_matches().unwrap();
+
+        assert!(anime.matches.get().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_feature_tolerances_incorrect_length() {
+        use crate::tolerance::FeatureTolerances;
+
+        let (source, target) = create_simple_source_target();
+        let tols = FeatureTolerances {
+            source_angle: Some(vec![5.0]),
+            ..Default::default()
+        };
+
+        let res = Anime::load_geometries_with_tolerances(
+            source.into_iter(),
+            target.into_iter(),
+            0.5,
+            5.0,
+            tols,
+        );
+        assert!(matches!(res, Err(AnimeError::IncorrectToleranceLength)));
+    }
+
+    #[test]
+    fn test_find_matches_checks_tolerance_length() {
+        use crate::tolerance::FeatureTolerances;
+
+        let (source, target) = create_simple_source_target();
+        let mut anime = Anime::load_geometries(source.into_iter(), target.into_iter(), 0.5, 5.0);
+        anime.feature_tolerances = Some(FeatureTolerances {
+            target_distance: Some(vec![1.0, 2.0]),
+            ..Default::default()
+        });
+        let res = anime.find_matches();
+        assert!(matches!(res, Err(AnimeError::IncorrectToleranceLength)));
+    }
+
+    #[test]
+    fn test_bearing() {
+        let north = geo_types::Line::new(coord! {x: 0.0, y: 0.0}, coord! {x: 0.0, y: 1.0});
+        let east = geo_types::Line::new(coord! {x: 0.0, y: 0.0}, coord! {x: 1.0, y: 0.0});
+        let west = geo_types::Line::new(coord! {x: 0.0, y: 0.0}, coord! {x: -1.0, y: 0.0});
+        assert_eq!(bearing(&north), 0.0);
+        assert_eq!(bearing(&east), 90.0);
+        assert_eq!(bearing(&west), 270.0);
+        assert_eq!(bearing_diff(350.0, 10.0), 20.0);
+        assert_eq!(bearing_diff(90.0, 270.0), 180.0);
+    }
+
+    fn create_opposing_source_target() -> (Vec<LineString>, Vec<LineString>) {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        // eastbound and westbound twins
+        let target = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.1}, coord! {x: 10.0, y: 0.1}]),
+            LineString::new(vec![coord! {x: 10.0, y: -0.1}, coord! {x: 0.0, y: -0.1}]),
+        ];
+        (source, target)
+    }
+
+    #[test]
+    fn test_undirected_matches_reversed() {
+        let (source, target) = create_opposing_source_target();
+        let anime = Anime::new(source.into_iter(), target.into_iter(), 0.5, 5.0);
+
+        let matches = anime.matches.get().unwrap();
+        let eastbound = &matches.get(&0).unwrap()[0];
+        let westbound = &matches.get(&1).unwrap()[0];
+        assert_eq!(eastbound.aligned_len, eastbound.shared_len);
+        assert_eq!(westbound.aligned_len, 0.0);
+        assert!(westbound.shared_len > 0.0);
+    }
+
+    #[test]
+    fn test_directed_skips_reversed() {
+        let (source, target) = create_opposing_source_target();
+        let mut anime = Anime::load_geometries(source.into_iter(), target.into_iter(), 0.5, 5.0);
+        anime.directed = true;
+        anime.find_matches().unwrap();
+
+        let matches = anime.matches.get().unwrap();
+        assert!(matches.contains_key(&0));
+        assert!(!matches.contains_key(&1));
+    }
+
+    #[test]
+    fn test_smoothing_matches_zig_zag() {
+        use crate::direction::DirectionSmoothing;
+
+        let zig_zag = || {
+            let coords = (0..=20)
+                .map(|k| coord! {x: k as f64, y: if k % 2 == 0 { 0.0 } else { 0.3 }})
+                .collect::<Vec<_>>();
+            vec![LineString::new(coords)]
+        };
+        let target = || {
+            vec![LineString::new(vec![
+                coord! {x: 0.0, y: 0.2},
+                coord! {x: 20.0, y: 0.2},
+            ])]
+        };
+
+        // each jittery segment is ~17 degrees off
+        let anime = Anime::new(zig_zag().into_iter(), target().into_iter(), 0.5, 5.0);
+        assert!(anime.matches.get().unwrap().is_empty());
+
+        let options = LoadOptions {
+            smoothing: Some(DirectionSmoothing {
+                window: 2,
+                min_len: 0.0,
+            }),
+            ..Default::default()
+        };
+        let mut anime = Anime::load_geometries_with_options(
+            zig_zag().into_iter(),
+            target().into_iter(),
+            0.5,
+            5.0,
+            options,
+        )
+        .unwrap();
+        anime.find_matches().unwrap();
+
+        let matches = anime.matches.get().unwrap();
+        let shared_len = matches.get(&0).unwrap()[0].shared_len;
+        // shared length is still measured along the jittery source
+        assert!(shared_len > 15.0);
+    }
+
+    #[test]
+    fn test_oriented_bearing() {
+        let west = geo_types::Line::new(coord! {x: 1.0, y: 0.0}, coord! {x: 0.0, y: 0.0});
+        assert_eq!(oriented_bearing(&west, west.slope()), bearing(&west));
+        // a smoothed slope keeps the line's orientation
+        assert_eq!(oriented_bearing(&west, 1.0), 225.0);
+    }
+
+    #[test]
+    fn test_prepare_densify_keeps_lengths() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+
+        let options = LoadOptions {
+            max_segment_len: Some(1.0),
+            ..Default::default()
+        };
+        let mut lens = Vec::new();
+        let tree = create_source_rtree(source.into_iter(), &mut lens, &options);
+
+        assert_eq!(tree.size(), 10);
+        assert_eq!(lens[0], 10.0);
+    }
+
+    #[test]
+    fn test_prepare_simplify_keeps_lengths() {
+        let coords = (0..=10)
+            .map(|k| coord! {x: k as f64, y: if k % 2 == 0 { 0.0 } else { 0.01 }})
+            .collect::<Vec<_>>();
+        let target = vec![LineString::new(coords)];
+        let original_len = target[0].length::<Euclidean>();
+
+        let options = LoadOptions {
+            simplify_tolerance: Some(0.1),
+            ..Default::default()
+        };
+        let mut lens = Vec::new();
+        let tree = create_target_rtree(target.into_iter(), &mut lens, |_| 0.5, &options);
+
+        assert_eq!(tree.size(), 1);
+        assert_eq!(lens[0], original_len);
+    }
+
     #[test]
     fn test_anime_error_display() {
         let err = AnimeError::IncorrectLength;
@@ -493,7 +1353,7 @@ mod tests {
         ])];
 
         let mut lens = Vec::new();
-        let tree = create_source_rtree(source.into_iter(), &mut lens);
+        let tree = create_source_rtree(source.into_iter(), &mut lens, &LoadOptions::default());
 
         assert_eq!(lens.len(), 1);
         assert_eq!(lens[0], 10.0);
@@ -508,10 +1368,249 @@ mod tests {
         ])];
 
         let mut lens = Vec::new();
-        let tree = create_target_rtree(target.into_iter(), &mut lens, 0.5);
+        let tree = create_target_rtree(
+            target.into_iter(),
+            &mut lens,
+            |_| 0.5,
+            &LoadOptions::default(),
+        );
 
         assert_eq!(lens.len(), 1);
         assert_eq!(lens[0], 10.0);
         assert!(tree.size() > 0);
     }
+
+    fn degenerate_lines() -> Vec<LineString> {
+        vec![
+            LineString::new(vec![]),
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: f64::NAN}, coord! {x: 10.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 0.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+        ]
+    }
+
+    #[test]
+    fn test_degenerate_reason() {
+        let reasons = degenerate_lines()
+            .iter()
+            .map(degenerate_reason)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            vec![
+                Some("empty"),
+                Some("single vertex"),
+                Some("non-finite coordinates"),
+                Some("zero length"),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_degenerate_geometries_unmatched() {
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let anime = Anime::new(degenerate_lines().into_iter(), target.into_iter(), 1.0, 5.0);
+
+        // every feature keeps its index
+        assert_eq!(anime.source_lens, vec![0.0, 0.0, 0.0, 0.0, 10.0]);
+        let matches = anime.matches.get().unwrap();
+        assert_eq!(matches[&0].len(), 1);
+        assert_eq!(matches[&0][0].source_index, 4);
+
+        let res = anime.get_matches().unwrap();
+        let source_weighted = res
+            .column_by_name("source_weighted")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<arrow::array::Float64Array>()
+            .unwrap();
+        assert!(source_weighted.values().iter().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn test_zero_length_component_lines_skipped() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 5.0, y: 0.0},
+            coord! {x: 5.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let mut lens = Vec::new();
+        let tree = create_source_rtree(source.into_iter(), &mut lens, &LoadOptions::default());
+
+        let mut positions = tree.iter().map(|x| x.data.2).collect::<Vec<_>>();
+        positions.sort();
+        assert_eq!(positions, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_strict_invalid_geometry() {
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let options = LoadOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let res = Anime::load_geometries_with_options(
+            target.clone().into_iter(),
+            degenerate_lines().into_iter().skip(1),
+            1.0,
+            5.0,
+            options,
+        );
+        let err = res.unwrap_err();
+        assert!(matches!(
+            err,
+            AnimeError::InvalidGeometry {
+                side: Side::Target,
+                index: 0,
+                reason: "single vertex"
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "invalid target geometry at index 0: single vertex"
+        );
+
+        // without strict mode the degenerate geometries are left unmatched
+        let mut anime = Anime::load_geometries_with_options(
+            target.into_iter(),
+            degenerate_lines().into_iter(),
+            1.0,
+            5.0,
+            LoadOptions::default(),
+        )
+        .unwrap();
+        anime.find_matches().unwrap();
+        assert_eq!(anime.target_lens.len(), 5);
+    }
+
+    #[test]
+    fn test_strict_stops_at_invalid_geometry() {
+        let options = LoadOptions {
+            strict: true,
+            ..Default::default()
+        };
+        // neither input is read past the first degenerate geometry
+        let unread = || std::iter::from_fn(|| panic!("read past an invalid geometry"));
+        let res = Anime::load_geometries_with_options(
+            degenerate_lines().into_iter().take(1).chain(unread()),
+            unread(),
+            1.0,
+            5.0,
+            options,
+        );
+        assert!(matches!(
+            res,
+            Err(AnimeError::InvalidGeometry {
+                side: Side::Source,
+                index: 0,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_load_options() {
+        for (options, option) in [
+            (
+                LoadOptions {
+                    max_segment_len: Some(0.0),
+                    ..Default::default()
+                },
+                "max_segment_len",
+            ),
+            (
+                LoadOptions {
+                    max_segment_len: Some(f64::NAN),
+                    ..Default::default()
+                },
+                "max_segment_len",
+            ),
+            (
+                LoadOptions {
+                    simplify_tolerance: Some(-1.0),
+                    ..Default::default()
+                },
+                "simplify_tolerance",
+            ),
+        ] {
+            // the inputs are never read
+            let res = Anime::load_geometries_with_options(
+                std::iter::from_fn(|| panic!("source was read")),
+                std::iter::from_fn(|| panic!("target was read")),
+                1.0,
+                5.0,
+                options,
+            );
+            assert!(matches!(
+                res,
+                Err(AnimeError::InvalidLoadOption { option: o, .. }) if o == option
+            ));
+        }
+    }
+
+    #[test]
+    fn test_nan_slope_not_matched() {
+        let x = geo_types::Line::new(coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0});
+        let y = geo_types::Line::new(coord! {x: 5.0, y: -5.0}, coord! {x: 5.0, y: 5.0});
+        let res = compare_components(
+            &x,
+            (0, f64::NAN, 0),
+            &y,
+            (0, f64::INFINITY, 0),
+            5.0,
+            1.0,
+            false,
+        );
+        assert!(res.is_none());
+
+        let res = compare_components(
+            &x,
+            (0, f64::NAN, 0),
+            &y,
+            (0, f64::INFINITY, 0),
+            5.0,
+            1.0,
+            true,
+        );
+        assert!(res.is_none());
+    }
+
+    #[test]
+    fn test_reversing_source_smoothed() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+            coord! {x: 0.0, y: 0.0},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 5.0, y: -5.0},
+            coord! {x: 5.0, y: 5.0},
+        ])];
+        let options = LoadOptions {
+            smoothing: Some(DirectionSmoothing {
+                window: 1,
+                min_len: 0.0,
+            }),
+            ..Default::default()
+        };
+        let mut anime = Anime::load_geometries_with_options(
+            source.into_iter(),
+            target.into_iter(),
+            1.0,
+            5.0,
+            options,
+        )
+        .unwrap();
+        anime.find_matches().unwrap();
+        assert!(anime.matches.get().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,351 @@
+use crate::{Anime, AnimeError, MatchCandidate, SegmentMatch};
+use arrow::array::{ArrayRef, Float64Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use geo::{Distance, Euclidean};
+use geo_types::Coord;
+use std::sync::Arc;
+
+/// Geometric similarity between a matched source and target
+///
+/// All metrics are computed over the shared portions of the component
+/// line pairs visited when matching, not the full geometries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchMetrics {
+    /// The length weighted mean lateral offset
+    pub mean_offset: f64,
+    /// The largest lateral offset
+    pub max_offset: f64,
+    /// The length weighted mean angle difference in degrees
+    pub mean_angle_diff: f64,
+    /// The discrete Hausdorff distance between the shared portions
+    pub hausdorff: f64,
+    /// The discrete Fréchet distance between the shared portions
+    pub frechet: f64,
+}
+
+impl MatchCandidate {
+    /// The length weighted mean lateral offset and angle difference
+    ///
+    /// Returns `None` when no component lines share a portion.
+    pub fn mean_offset_and_angle(&self) -> Option<(f64, f64)> {
+        if self.segments.is_empty() {
+            return None;
+        }
+
+        // weight by shared length, falling back to equal weights
+        let total_len: f64 = self.segments.iter().map(|s| s.shared_len()).sum();
+        let weight = |s: &SegmentMatch| {
+            if total_len > 0.0 {
+                s.shared_len() / total_len
+            } else {
+                1.0 / self.segments.len() as f64
+            }
+        };
+
+        let mean_offset = self.segments.iter().map(|s| s.offset() * weight(s)).sum();
+        let mean_angle_diff = self.segments.iter().map(|s| s.angle_diff * weight(s)).sum();
+        Some((mean_offset, mean_angle_diff))
+    }
+
+    /// Compute similarity metrics from the matched component lines
+    ///
+    /// The Fréchet distance follows the target in the source's direction
+    /// when less than half of the shared length is aligned, as when an
+    /// undirected match pairs lines digitised in opposite directions.
+    /// Returns `None` when no component lines share a portion.
+    pub fn metrics(&self) -> Option<MatchMetrics> {
+        let (mean_offset, mean_angle_diff) = self.mean_offset_and_angle()?;
+        let max_offset = self
+            .segments
+            .iter()
+            .map(|s| {
+                let (d1, d2) = s.offsets();
+                d1.max(d2)
+            })
+            .fold(0.0, f64::max);
+
+        let (source_pts, mut target_pts) = ordered_points(&self.segments);
+        // follow the target in the source's direction when they mostly oppose
+        let is_reversed = self.aligned_len * 2.0 < self.shared_len;
+        if is_reversed {
+            target_pts.reverse();
+        }
+
+        Some(MatchMetrics {
+            mean_offset,
+            max_offset,
+            mean_angle_diff,
+            hausdorff: discrete_hausdorff(&source_pts, &target_pts),
+            frechet: discrete_frechet(&source_pts, &target_pts),
+        })
+    }
+}
+
+impl Anime {
+    /// Extract the matches with geometric similarity metrics
+    ///
+    /// Contains the columns of [`Anime::get_matches`] followed by
+    /// `mean_offset`, `max_offset`, `mean_angle_diff`, `hausdorff`, and
+    /// `frechet`. See [`MatchMetrics`]. The metrics are null for pairs
+    /// that share no portion of their component lines. Requires
+    /// [`Anime::record_segments`].
+    pub fn get_matches_with_metrics(&self) -> Result<RecordBatch, AnimeError> {
+        self.require_segments()?;
+        let base = self.get_matches()?;
+        let inner = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+
+        let metrics = inner
+            .values()
+            .flatten()
+            .map(|mc| mc.metrics())
+            .collect::<Vec<_>>();
+
+        let column = |f: fn(&MatchMetrics) -> f64| -> ArrayRef {
+            Arc::new(
+                metrics
+                    .iter()
+                    .map(|m| m.as_ref().map(f))
+                    .collect::<Float64Array>(),
+            )
+        };
+
+        let mut fields = base.schema().fields().to_vec();
+        let mut columns = base.columns().to_vec();
+        for name in [
+            "mean_offset",
+            "max_offset",
+            "mean_angle_diff",
+            "hausdorff",
+            "frechet",
+        ] {
+            fields.push(Arc::new(Field::new(name, DataType::Float64, true)));
+        }
+        columns.push(column(|m| m.mean_offset));
+        columns.push(column(|m| m.max_offset));
+        columns.push(column(|m| m.mean_angle_diff));
+        columns.push(column(|m| m.hausdorff));
+        columns.push(column(|m| m.frechet));
+
+        let res = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+            .expect("All arrays should be identical lengths");
+        Ok(res)
+    }
+}
+
+// The end points of the shared portions ordered along the source and target
+//
+// Portions without length, where component lines only touch, have no
+// direction to order them by so they are skipped unless nothing else is shared.
+fn ordered_points(segments: &[SegmentMatch]) -> (Vec<Coord>, Vec<Coord>) {
+    let shared = segments
+        .iter()
+        .filter(|s| s.shared_len() > 0.0)
+        .cloned()
+        .collect::<Vec<_>>();
+    let segments = if shared.is_empty() { segments } else { &shared };
+
+    // position of a line's start along its own direction
+    let position = |l: &geo_types::Line| {
+        let d = l.delta();
+        let len = d.x.hypot(d.y);
+        if len > 0.0 {
+            (l.start.x * d.x + l.start.y * d.y) / len
+        } else {
+            0.0
+        }
+    };
+
+    let mut source = segments
+        .iter()
+        .map(|s| (s.source_component, position(&s.source), s.source))
+        .collect::<Vec<_>>();
+    source.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+    let mut target = segments
+        .iter()
+        .map(|s| (s.target_component, position(&s.target), s.target))
+        .collect::<Vec<_>>();
+    target.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+    let flatten = |v: Vec<(usize, f64, geo_types::Line)>| {
+        v.into_iter()
+            .flat_map(|(_, _, l)| [l.start, l.end])
+            .collect::<Vec<_>>()
+    };
+    (flatten(source), flatten(target))
+}
+
+fn coord_distance(a: &Coord, b: &Coord) -> f64 {
+    Euclidean::distance(geo_types::Point(*a), geo_types::Point(*b))
+}
+
+/// Discrete Hausdorff distance between two sets of points
+pub(crate) fn discrete_hausdorff(a: &[Coord], b: &[Coord]) -> f64 {
+    let directed = |a: &[Coord], b: &[Coord]| {
+        a.iter()
+            .map(|ai| {
+                b.iter()
+                    .map(|bi| coord_distance(ai, bi))
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .fold(0.0, f64::max)
+    };
+    directed(a, b).max(directed(b, a))
+}
+
+/// Discrete Fréchet distance between two sequences of points
+pub(crate) fn discrete_frechet(a: &[Coord], b: &[Coord]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    // dynamic programming over the coupling table one row at a time
+    let mut prev: Vec<f64> = vec![0.0; b.len()];
+    let mut cur: Vec<f64> = vec![0.0; b.len()];
+    for (i, ai) in a.iter().enumerate() {
+        for (j, bj) in b.iter().enumerate() {
+            let d = coord_distance(ai, bj);
+            cur[j] = match (i, j) {
+                (0, 0) => d,
+                (0, _) => cur[j - 1].max(d),
+                (_, 0) => prev[0].max(d),
+                _ => prev[j].min(prev[j - 1]).min(cur[j - 1]).max(d),
+            };
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len() - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::AsArray;
+    use arrow::datatypes::Float64Type;
+    use geo_types::{coord, LineString};
+
+    #[test]
+    fn test_discrete_hausdorff() {
+        let a = vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}];
+        let b = vec![coord! {x: 0.0, y: 1.0}, coord! {x: 10.0, y: 3.0}];
+        assert_eq!(discrete_hausdorff(&a, &b), 3.0);
+    }
+
+    #[test]
+    fn test_discrete_frechet_respects_order() {
+        let a = vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}];
+        let b = vec![coord! {x: 0.0, y: 1.0}, coord! {x: 10.0, y: 1.0}];
+        let b_rev = vec![coord! {x: 10.0, y: 1.0}, coord! {x: 0.0, y: 1.0}];
+
+        assert_eq!(discrete_frechet(&a, &b), 1.0);
+        assert!(discrete_frechet(&a, &b_rev) > 10.0);
+        // hausdorff ignores order
+        assert_eq!(discrete_hausdorff(&a, &b_rev), 1.0);
+    }
+
+    #[test]
+    fn test_match_metrics_parallel_offset() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 5.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.5},
+            coord! {x: 10.0, y: 0.5},
+        ])];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+
+        let mc = &anime.matches.get().unwrap()[&0][0];
+        assert_eq!(mc.segments.len(), 2);
+
+        let metrics = mc.metrics().unwrap();
+        assert_eq!(metrics.mean_offset, 0.5);
+        assert_eq!(metrics.max_offset, 0.5);
+        assert_eq!(metrics.mean_angle_diff, 0.0);
+        assert_eq!(metrics.hausdorff, 0.5);
+        assert_eq!(metrics.frechet, 0.5);
+    }
+
+    #[test]
+    fn test_match_metrics_diverging() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.5},
+        ])];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+
+        let metrics = anime.matches.get().unwrap()[&0][0].metrics().unwrap();
+        assert!(metrics.max_offset > metrics.mean_offset);
+        assert!(metrics.mean_angle_diff > 2.0);
+    }
+
+    #[test]
+    fn test_get_matches_with_metrics() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.2},
+            coord! {x: 10.0, y: 0.2},
+        ])];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+
+        let res = anime.get_matches_with_metrics().unwrap();
+        let base = anime.get_matches().unwrap();
+        assert_eq!(res.num_rows(), base.num_rows());
+        assert_eq!(res.num_columns(), base.num_columns() + 5);
+
+        let mean_offset = res
+            .column_by_name("mean_offset")
+            .unwrap()
+            .as_primitive::<Float64Type>();
+        assert!((mean_offset.value(0) - 0.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_segments_not_recorded() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.2},
+            coord! {x: 10.0, y: 0.2},
+        ])];
+        let anime = Anime::new(source.into_iter(), target.into_iter(), 1.0, 5.0);
+
+        let mc = &anime.matches.get().unwrap()[&0][0];
+        assert!(mc.segments.is_empty());
+        assert_eq!(mc.shared_len, 10.0);
+        assert!(matches!(
+            anime.get_matches_with_metrics(),
+            Err(AnimeError::SegmentsNotRecorded)
+        ));
+    }
+
+    #[test]
+    fn test_match_metrics_reversed_target() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+            coord! {x: 20.0, y: 0.0},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 20.0, y: 0.5},
+            coord! {x: 10.0, y: 0.5},
+            coord! {x: 0.0, y: 0.5},
+        ])];
+        let anime = Anime::new_with_segments(source.into_iter(), target.into_iter(), 1.0, 5.0);
+
+        let mc = &anime.matches.get().unwrap()[&0][0];
+        let metrics = mc.metrics().unwrap();
+        assert_eq!(metrics.hausdorff, 0.5);
+        assert_eq!(metrics.frechet, 0.5);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::assign::assign_greedy;
+use crate::{bearing, bearing_diff, Anime, AnimeError};
+use arrow::array::{Float64Array, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use geo_types::{Coord, Line};
+use rstar::primitives::GeomWithData;
+use rstar::RTree;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+/// A junction derived from the end points of linestrings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Junction {
+    /// The location of the first end point snapped to the junction
+    pub coord: Coord,
+    /// The index of each incident linestring. A linestring that starts
+    /// and ends at the same junction appears twice.
+    pub edges: Vec<usize>,
+    /// The bearing, in degrees, of each incident linestring leaving the junction
+    pub bearings: Vec<f64>,
+}
+
+impl Junction {
+    /// The number of incident linestrings
+    pub fn degree(&self) -> usize {
+        self.edges.len()
+    }
+}
+
+/// Parameters used when matching junctions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeMatchOptions {
+    /// The maximum distance between matched junctions
+    pub distance_tolerance: f64,
+    /// The maximum difference, in degrees, between paired incident bearings
+    pub angle_tolerance: f64,
+    /// End points within this distance of each other form one junction
+    pub snap: f64,
+    /// Pairs scoring less than this are not matched
+    pub min_score: f64,
+}
+
+/// Junctions of both inputs and the crosswalk between them
+#[derive(Debug, Clone)]
+pub struct NodeMatches {
+    pub source: Vec<Junction>,
+    pub target: Vec<Junction>,
+    /// Matched junctions with columns `source_node`, `target_node`,
+    /// `distance`, and `score`. Node ids index `source` and `target`.
+    pub crosswalk: RecordBatch,
+}
+
+impl Anime {
+    /// Derive junctions from the end points of the source and target linestrings
+    pub fn junctions(&self, snap: f64) -> (Vec<Junction>, Vec<Junction>) {
+        let source = feature_ends(self.source_tree.iter().map(|cx| (**cx.geom(), cx.data)));
+        let target = feature_ends(self.target_tree.iter().map(|cy| (cy.geom().0, cy.data)));
+        (
+            build_junctions(&source, snap),
+            build_junctions(&target, snap),
+        )
+    }
+
+    /// Match junctions between the source and target
+    ///
+    /// Target junctions within `distance_tolerance` of a source junction are
+    /// candidates. Each candidate is scored as the mean of four components
+    /// between 0 and 1:
+    ///
+    /// - distance: one minus the distance divided by `distance_tolerance`
+    /// - degree: the smaller degree divided by the larger
+    /// - bearings: the share of incident bearings that pair up within
+    ///   `angle_tolerance`
+    /// - edges: the share of incident source linestrings matched to an
+    ///   incident target linestring in `self.matches`
+    ///
+    /// Candidates scoring at least `min_score` are assigned greedily 1:1
+    /// from the highest score.
+    pub fn match_nodes(&self, options: &NodeMatchOptions) -> Result<NodeMatches, AnimeError> {
+        let inner = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+        let edge_matches = inner
+            .iter()
+            .flat_map(|(j, items)| items.iter().map(move |mc| (mc.source_index, *j)))
+            .collect::<BTreeSet<_>>();
+
+        let (source, target) = self.junctions(options.snap);
+        let target_tree = RTree::bulk_load(
+            target
+                .iter()
+                .enumerate()
+                .map(|(k, n)| GeomWithData::new([n.coord.x, n.coord.y], k))
+                .collect(),
+        );
+
+        let max_dist_2 = options.distance_tolerance * options.distance_tolerance;
+        let mut candidates = Vec::new();
+        for (s, sn) in source.iter().enumerate() {
+            for (tn, d2) in
+                target_tree.nearest_neighbor_iter_with_distance_2(&[sn.coord.x, sn.coord.y])
+            {
+                if d2 > max_dist_2 {
+                    break;
+                }
+                let t = tn.data;
+                let score = node_score(sn, &target[t], d2.sqrt(), options, &edge_matches);
+                if score >= options.min_score {
+                    candidates.push((s, t, score));
+                }
+            }
+        }
+
+        let mut pairs = assign_greedy(candidates, true);
+        pairs.sort_by_key(|p| p.0);
+
+        let distance = |s: usize, t: usize| {
+            let d = source[s].coord - target[t].coord;
+            d.x.hypot(d.y)
+        };
+        let schema = Schema::new(vec![
+            Field::new("source_node", DataType::Int32, false),
+            Field::new("target_node", DataType::Int32, false),
+            Field::new("distance", DataType::Float64, false),
+            Field::new("score", DataType::Float64, false),
+        ]);
+        let crosswalk = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from_iter_values(
+                    pairs.iter().map(|(s, _, _)| *s as i32),
+                )),
+                Arc::new(Int32Array::from_iter_values(
+                    pairs.iter().map(|(_, t, _)| *t as i32),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    pairs.iter().map(|(s, t, _)| distance(*s, *t)),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    pairs.iter().map(|(_, _, score)| *score),
+                )),
+            ],
+        )
+        .expect("All arrays should be identical lengths");
+
+        Ok(NodeMatches {
+            source,
+            target,
+            crosswalk,
+        })
+    }
+}
+
+// the position and line of the first and last component lines
+type ComponentEnds = ((usize, Line), (usize, Line));
+
+// the first and last component lines of each feature in a tree
+fn feature_ends(
+    components: impl Iterator<Item = (Line, crate::ComponentData)>,
+) -> Vec<(usize, Line, Line)> {
+    let mut ends: BTreeMap<usize, ComponentEnds> = BTreeMap::new();
+    for (line, (idx, _, k)) in components {
+        let entry = ends.entry(idx).or_insert(((k, line), (k, line)));
+        if k < entry.0 .0 {
+            entry.0 = (k, line);
+        }
+        if k > entry.1 .0 {
+            entry.1 = (k, line);
+        }
+    }
+    ends.into_iter()
+        .map(|(idx, ((_, first), (_, last)))| (idx, first, last))
+        .collect()
+}
+
+fn build_junctions(ends: &[(usize, Line, Line)], snap: f64) -> Vec<Junction> {
+    let mut junctions: Vec<Junction> = Vec::new();
+    let mut tree: RTree<GeomWithData<[f64; 2], usize>> = RTree::new();
+    let snap_2 = snap * snap;
+
+    for (idx, first, last) in ends {
+        // the start of the first line and the end of the last, both leaving the junction
+        for leaving in [*first, Line::new(last.end, last.start)] {
+            let pt = [leaving.start.x, leaving.start.y];
+            let existing = tree
+                .nearest_neighbor_iter_with_distance_2(&pt)
+                .next()
+                .filter(|(_, d2)| *d2 <= snap_2)
+                .map(|(n, _)| n.data);
+
+            let k = existing.unwrap_or_else(|| {
+                junctions.push(Junction {
+                    coord: leaving.start,
+                    edges: Vec::new(),
+                    bearings: Vec::new(),
+                });
+                let k = junctions.len() - 1;
+                tree.insert(GeomWithData::new(pt, k));
+                k
+            });
+            junctions[k].edges.push(*idx);
+            junctions[k].bearings.push(bearing(&leaving));
+        }
+    }
+    junctions
+}
+
+fn node_score(
+    source: &Junction,
+    target: &Junction,
+    distance: f64,
+    options: &NodeMatchOptions,
+    edge_matches: &BTreeSet<(usize, usize)>,
+) -> f64 {
+    let max_degree = source.degree().max(target.degree()) as f64;
+    let distance_score = if options.distance_tolerance > 0.0 {
+        1.0 - distance / options.distance_tolerance
+    } else {
+        1.0
+    };
+    let degree_score = source.degree().min(target.degree()) as f64 / max_degree;
+
+    // pair bearings greedily from the smallest difference
+    let mut diffs = source
+        .bearings
+        .iter()
+        .enumerate()
+        .flat_map(|(a, ba)| {
+            target
+                .bearings
+                .iter()
+                .enumerate()
+                .map(move |(b, bb)| (a, b, bearing_diff(*ba, *bb)))
+        })
+        .collect::<Vec<_>>();
+    diffs.sort_by(|x, y| x.2.total_cmp(&y.2));
+    let mut used_source = BTreeSet::new();
+    let mut used_target = BTreeSet::new();
+    let n_paired = diffs
+        .into_iter()
+        .filter(|(a, b, diff)| {
+            *diff <= options.angle_tolerance && used_source.insert(*a) && used_target.insert(*b)
+        })
+        .count();
+    let bearing_score = n_paired as f64 / max_degree;
+
+    let n_matched = source
+        .edges
+        .iter()
+        .filter(|i| {
+            target
+                .edges
+                .iter()
+                .any(|j| edge_matches.contains(&(**i, *j)))
+        })
+        .count();
+    let edge_score = n_matched as f64 / source.degree() as f64;
+
+    (distance_score + degree_score + bearing_score + edge_score) / 4.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int32Type;
+    use geo_types::{coord, LineString};
+
+    // a plus shaped junction of four linestrings centred on (x, y)
+    fn plus(x: f64, y: f64) -> Vec<LineString> {
+        [(10.0, 0.0), (0.0, 10.0), (-10.0, 0.0), (0.0, -10.0)]
+            .iter()
+            .map(|(dx, dy)| {
+                LineString::new(vec![coord! {x: x, y: y}, coord! {x: x + dx, y: y + dy}])
+            })
+            .collect()
+    }
+
+    fn options() -> NodeMatchOptions {
+        NodeMatchOptions {
+            distance_tolerance: 2.0,
+            angle_tolerance: 10.0,
+            snap: 0.01,
+            min_score: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_junctions() {
+        let anime = Anime::load_geometries(
+            plus(0.0, 0.0).into_iter(),
+            plus(0.5, 0.5).into_iter(),
+            1.0,
+            10.0,
+        );
+        let (source, target) = anime.junctions(0.01);
+
+        // one centre and four dead ends
+        assert_eq!(source.len(), 5);
+        assert_eq!(target.len(), 5);
+        let centre = source.iter().find(|n| n.degree() == 4).unwrap();
+        assert_eq!(centre.coord, coord! {x: 0.0, y: 0.0});
+        let mut bearings = centre.bearings.clone();
+        bearings.sort_by(f64::total_cmp);
+        assert_eq!(bearings, vec![0.0, 90.0, 180.0, 270.0]);
+    }
+
+    #[test]
+    fn test_match_nodes() {
+        let anime = Anime::new(
+            plus(0.0, 0.0).into_iter(),
+            plus(0.5, 0.5).into_iter(),
+            1.0,
+            10.0,
+        );
+        let res = anime.match_nodes(&options()).unwrap();
+        assert_eq!(res.crosswalk.num_rows(), 5);
+
+        // the centres are matched to each other
+        let source_node = res.crosswalk.column(0).as_primitive::<Int32Type>();
+        let target_node = res.crosswalk.column(1).as_primitive::<Int32Type>();
+        let centre = (0..5)
+            .find(|k| res.source[source_node.value(*k) as usize].degree() == 4)
+            .unwrap();
+        assert_eq!(res.target[target_node.value(centre) as usize].degree(), 4);
+    }
+
+    #[test]
+    fn test_match_nodes_uses_structure() {
+        // a dead end is closer to the source centre than the target centre
+        let mut target = plus(1.5, 0.0);
+        target.push(LineString::new(vec![
+            coord! {x: -0.5, y: 0.0},
+            coord! {x: -0.5, y: -5.0},
+        ]));
+        let anime = Anime::new(plus(0.0, 0.0).into_iter(), target.into_iter(), 2.0, 10.0);
+        let res = anime.match_nodes(&options()).unwrap();
+
+        let source_node = res.crosswalk.column(0).as_primitive::<Int32Type>();
+        let target_node = res.crosswalk.column(1).as_primitive::<Int32Type>();
+        let k = (0..res.crosswalk.num_rows())
+            .find(|k| res.source[source_node.value(*k) as usize].degree() == 4)
+            .unwrap();
+        let matched = &res.target[target_node.value(k) as usize];
+        assert_eq!(matched.degree(), 4);
+    }
+}
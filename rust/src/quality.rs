@@ -0,0 +1,353 @@
+use crate::{create_target_rtree, LoadOptions, TargetTree};
+use arrow::array::{Float64Array, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use geo::{BoundingRect, Euclidean, Intersects, Length};
+use geo_types::{coord, Coord, Line, LineString, Point, Polygon, Rect};
+use rstar::primitives::{GeomWithData, Rectangle};
+use rstar::{RTree, AABB};
+use std::sync::Arc;
+
+/// Accuracy and completeness curves of the source against the target
+///
+/// Implements the buffer-based measure of Goodchild and Hunter (1997), "A
+/// simple positional accuracy measure for linear features". For each buffer width the proportion of source length within that
+/// distance of the target is the positional accuracy, and the proportion
+/// of target length within that distance of the source is the completeness.
+///
+/// When `zones` are provided each component line is assigned to the first
+/// zone containing its midpoint, and a curve is reported per zone. Component
+/// lines outside every zone are ignored. Without zones the `zone` column is
+/// null.
+///
+/// The returned table has one row per zone and width with columns `zone`,
+/// `width`, `source_len`, `source_within_len`, `accuracy`, `target_len`,
+/// `target_within_len`, and `completeness`.
+pub fn buffer_curves(
+    source: impl Iterator<Item = LineString>,
+    target: impl Iterator<Item = LineString>,
+    widths: &[f64],
+    zones: Option<&[Polygon]>,
+) -> RecordBatch {
+    let source = source.collect::<Vec<_>>();
+    let target = target.collect::<Vec<_>>();
+    let max_width = widths.iter().copied().fold(0.0, f64::max);
+
+    let source_index = buffered_index(&source, max_width);
+    let target_index = buffered_index(&target, max_width);
+    let zone_index = zones.map(|z| (z, zone_index(z)));
+    let n_zones = zones.map_or(1, |z| z.len());
+
+    let source_totals = within_lens(&source, &target_index, widths, zone_index.as_ref(), n_zones);
+    let target_totals = within_lens(&target, &source_index, widths, zone_index.as_ref(), n_zones);
+
+    let mut rows = Vec::with_capacity(n_zones * widths.len());
+    for zone in 0..n_zones {
+        let (source_len, source_within) = &source_totals[zone];
+        let (target_len, target_within) = &target_totals[zone];
+        for (w, width) in widths.iter().enumerate() {
+            rows.push((
+                zones.map(|_| zone as i32),
+                *width,
+                *source_len,
+                source_within[w],
+                proportion(source_within[w], *source_len),
+                *target_len,
+                target_within[w],
+                proportion(target_within[w], *target_len),
+            ));
+        }
+    }
+
+    let schema = Schema::new(vec![
+        Field::new("zone", DataType::Int32, true),
+        Field::new("width", DataType::Float64, false),
+        Field::new("source_len", DataType::Float64, false),
+        Field::new("source_within_len", DataType::Float64, false),
+        Field::new("accuracy", DataType::Float64, false),
+        Field::new("target_len", DataType::Float64, false),
+        Field::new("target_within_len", DataType::Float64, false),
+        Field::new("completeness", DataType::Float64, false),
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(Int32Array::from_iter(rows.iter().map(|r| r.0))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.1))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.2))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.3))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.4))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.5))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.6))),
+            Arc::new(Float64Array::from_iter_values(rows.iter().map(|r| r.7))),
+        ],
+    )
+    .expect("All arrays should be identical lengths")
+}
+
+/// Square grid cells covering `bounds`, for use as zones
+///
+/// Cells are ordered row by row from the lower left corner.
+pub fn grid(bounds: Rect, size: f64) -> Vec<Polygon> {
+    let n_x = (bounds.width() / size).ceil().max(1.0) as usize;
+    let n_y = (bounds.height() / size).ceil().max(1.0) as usize;
+    let min = bounds.min();
+    (0..n_y)
+        .flat_map(|row| {
+            (0..n_x).map(move |col| {
+                let ll = coord! {x: min.x + col as f64 * size, y: min.y + row as f64 * size};
+                Rect::new(ll, ll + coord! {x: size, y: size}).to_polygon()
+            })
+        })
+        .collect()
+}
+
+type ZoneIndex = RTree<GeomWithData<Rectangle<Point>, usize>>;
+
+fn zone_index(zones: &[Polygon]) -> ZoneIndex {
+    RTree::bulk_load(
+        zones
+            .iter()
+            .enumerate()
+            .filter_map(|(k, z)| {
+                let bb = z.bounding_rect()?;
+                let rect = Rectangle::from_corners(bb.min().into(), bb.max().into());
+                Some(GeomWithData::new(rect, k))
+            })
+            .collect(),
+    )
+}
+
+// the reference network indexed with lines padded by the widest buffer
+fn buffered_index(lines: &[LineString], max_width: f64) -> TargetTree {
+    let mut lens = Vec::with_capacity(lines.len());
+    create_target_rtree(
+        lines.iter().cloned(),
+        &mut lens,
+        |_| max_width,
+        &LoadOptions::default(),
+    )
+}
+
+// the length, and the length within each width of the reference, per zone
+fn within_lens(
+    tested: &[LineString],
+    reference: &TargetTree,
+    widths: &[f64],
+    zones: Option<&(&[Polygon], ZoneIndex)>,
+    n_zones: usize,
+) -> Vec<(f64, Vec<f64>)> {
+    let mut totals = vec![(0.0, vec![0.0; widths.len()]); n_zones];
+    for line in tested.iter().flat_map(|ls| ls.lines()) {
+        let len = line.length::<Euclidean>();
+        if len == 0.0 || !len.is_finite() {
+            continue;
+        }
+        let zone = match zones {
+            Some((zones, index)) => {
+                match find_zone(zones, index, line.start + line.delta() * 0.5) {
+                    Some(zone) => zone,
+                    None => continue,
+                }
+            }
+            None => 0,
+        };
+
+        let bb = line.bounding_rect();
+        let env = AABB::from_corners(Point::from(bb.min()), Point::from(bb.max()));
+        let candidates = reference
+            .locate_in_envelope_intersecting(&env)
+            .map(|c| c.geom().0)
+            .collect::<Vec<_>>();
+
+        let (total, within) = &mut totals[zone];
+        *total += len;
+        for (w, width) in widths.iter().enumerate() {
+            let intervals = candidates
+                .iter()
+                .filter_map(|other| within_interval(&line, other, *width))
+                .collect::<Vec<_>>();
+            within[w] += union_len(intervals) * len;
+        }
+    }
+    totals
+}
+
+fn find_zone(zones: &[Polygon], index: &ZoneIndex, pt: Coord) -> Option<usize> {
+    let p = Point::from(pt);
+    index
+        .locate_in_envelope_intersecting(&AABB::from_point(p))
+        .filter(|z| zones[z.data].intersects(&p))
+        .map(|z| z.data)
+        .min()
+}
+
+/// The interval of `x`, as positions between 0 and 1, within `width` of `y`
+///
+/// The points within `width` of a line form a convex capsule: a rectangle
+/// with a disc at each end. The interval is the hull of the intervals
+/// within each part.
+fn within_interval(x: &Line, y: &Line, width: f64) -> Option<(f64, f64)> {
+    let parts = [
+        disc_interval(x, y.start, width),
+        disc_interval(x, y.end, width),
+        rect_interval(x, y, width),
+    ];
+    parts
+        .into_iter()
+        .flatten()
+        .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+}
+
+fn disc_interval(x: &Line, centre: Coord, r: f64) -> Option<(f64, f64)> {
+    let d = x.delta();
+    let v = x.start - centre;
+    let a = d.x * d.x + d.y * d.y;
+    let b = 2.0 * (d.x * v.x + d.y * v.y);
+    let c = v.x * v.x + v.y * v.y - r * r;
+    let disc = b * b - 4.0 * a * c;
+    if a == 0.0 || disc < 0.0 {
+        return None;
+    }
+    let root = disc.sqrt();
+    clip((-b - root) / (2.0 * a), (-b + root) / (2.0 * a))
+}
+
+fn rect_interval(x: &Line, y: &Line, width: f64) -> Option<(f64, f64)> {
+    let len = y.length::<Euclidean>();
+    if len == 0.0 {
+        return None;
+    }
+    let u = y.delta() / len;
+    let n = coord! {x: -u.y, y: u.x};
+    let v = x.start - y.start;
+    let d = x.delta();
+    let dot = |a: Coord, b: Coord| a.x * b.x + a.y * b.y;
+
+    // position along and across `y` are both linear in t
+    let mut lo: f64 = 0.0;
+    let mut hi: f64 = 1.0;
+    for (s0, s1, min, max) in [
+        (dot(v, u), dot(d, u), 0.0, len),
+        (dot(v, n), dot(d, n), -width, width),
+    ] {
+        if s1 == 0.0 {
+            if s0 < min || s0 > max {
+                return None;
+            }
+        } else {
+            let (t0, t1) = ((min - s0) / s1, (max - s0) / s1);
+            lo = lo.max(t0.min(t1));
+            hi = hi.min(t0.max(t1));
+        }
+    }
+    (lo <= hi).then_some((lo, hi))
+}
+
+fn clip(lo: f64, hi: f64) -> Option<(f64, f64)> {
+    let (lo, hi) = (lo.max(0.0), hi.min(1.0));
+    (lo <= hi).then_some((lo, hi))
+}
+
+fn union_len(mut intervals: Vec<(f64, f64)>) -> f64 {
+    intervals.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut total = 0.0;
+    let mut current: Option<(f64, f64)> = None;
+    for (a, b) in intervals {
+        current = match current {
+            Some((lo, hi)) if a <= hi => Some((lo, hi.max(b))),
+            Some((lo, hi)) => {
+                total += hi - lo;
+                Some((a, b))
+            }
+            None => Some((a, b)),
+        };
+    }
+    total + current.map_or(0.0, |(lo, hi)| hi - lo)
+}
+
+fn proportion(part: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        part / total
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::AsArray;
+    use arrow::datatypes::Float64Type;
+
+    fn line(x0: f64, x1: f64, y: f64) -> LineString {
+        LineString::new(vec![coord! {x: x0, y: y}, coord! {x: x1, y: y}])
+    }
+
+    #[test]
+    fn test_within_interval() {
+        let x = Line::new(coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0});
+        let y = Line::new(coord! {x: 2.0, y: 1.0}, coord! {x: 5.0, y: 1.0});
+
+        assert_eq!(within_interval(&x, &y, 0.5), None);
+        assert_eq!(within_interval(&x, &y, 1.0), Some((0.2, 0.5)));
+        let (lo, hi) = within_interval(&x, &y, 2.0).unwrap();
+        assert!((lo - (2.0 - 3f64.sqrt()) / 10.0).abs() < 1e-12);
+        assert!((hi - (5.0 + 3f64.sqrt()) / 10.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_union_len() {
+        assert_eq!(union_len(vec![]), 0.0);
+        assert_eq!(
+            union_len(vec![(0.5, 0.75), (0.0, 0.25), (0.125, 0.375)]),
+            0.625
+        );
+    }
+
+    #[test]
+    fn test_buffer_curves() {
+        let source = vec![line(0.0, 10.0, 0.0)];
+        let target = vec![line(0.0, 5.0, 0.5)];
+        let res = buffer_curves(
+            source.into_iter(),
+            target.into_iter(),
+            &[0.25, 0.5, 1.0],
+            None,
+        );
+        assert_eq!(res.num_rows(), 3);
+        assert!(res.column(0).is_null(0));
+
+        let accuracy = res.column(4).as_primitive::<Float64Type>();
+        let completeness = res.column(7).as_primitive::<Float64Type>();
+        assert_eq!(accuracy.value(0), 0.0);
+        assert!((accuracy.value(1) - 0.5).abs() < 1e-12);
+        assert!((accuracy.value(2) - (5.0 + 0.75f64.sqrt()) / 10.0).abs() < 1e-12);
+        assert_eq!(completeness.value(0), 0.0);
+        assert_eq!(completeness.value(1), 1.0);
+    }
+
+    #[test]
+    fn test_buffer_curves_zoned() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+            coord! {x: 20.0, y: 0.0},
+        ])];
+        let target = vec![line(0.0, 10.0, 0.5)];
+        let zones = grid(
+            Rect::new(coord! {x: 0.0, y: -5.0}, coord! {x: 20.0, y: 5.0}),
+            10.0,
+        );
+        assert_eq!(zones.len(), 2);
+
+        let res = buffer_curves(source.into_iter(), target.into_iter(), &[1.0], Some(&zones));
+        assert_eq!(res.num_rows(), 2);
+
+        let accuracy = res.column(4).as_primitive::<Float64Type>();
+        assert_eq!(accuracy.value(0), 1.0);
+        assert!(accuracy.value(1) < 0.1);
+        let target_len = res.column(5).as_primitive::<Float64Type>();
+        assert_eq!(target_len.value(0), 10.0);
+        assert_eq!(target_len.value(1), 0.0);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::coverage::param;
+use crate::{angle_diff, compare_segments, create_target_rtree, LoadOptions};
+use arrow::array::{Float64Array, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use geo::{Euclidean, Length};
+use geo_types::Line;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+// the covered parameter intervals of each component line of a feature
+// keyed by (measured feature, other feature, component position)
+type Covered = BTreeMap<(usize, usize, usize), (Line, Vec<(f64, f64)>)>;
+
+/// Find duplicate or overlapping features within one dataset
+///
+/// A single R*-tree is built from `x` and intersected with itself so
+/// each feature is compared against every other feature but never itself.
+/// Component lines match under the same distance and angle rules as
+/// [`crate::Anime::find_matches`].
+///
+/// Each unordered pair is reported once with `feature_a < feature_b`.
+/// `shared_len_a` is the length of `feature_a` within the tolerances of
+/// `feature_b` and `shared_len_b` the length of `feature_b` within the
+/// tolerances of `feature_a`. Portions of a feature shared with several
+/// component lines of the other, such as both legs of a hairpin, are
+/// counted once. `coverage_a` and `coverage_b` divide each shared length
+/// by the length of its own feature, so a pair where both are close to 1
+/// is a duplicate.
+pub fn self_matches(
+    x: impl Iterator<Item = geo_types::LineString>,
+    distance_tolerance: f64,
+    angle_tolerance: f64,
+) -> RecordBatch {
+    let mut lens = Vec::new();
+    let tree = create_target_rtree(
+        x,
+        &mut lens,
+        |_| distance_tolerance,
+        &LoadOptions::default(),
+    );
+
+    let mut covered = Covered::new();
+    for (ca, cb) in tree.intersection_candidates_with_other_tree(&tree) {
+        let (a, a_slope, k) = ca.data;
+        let (b, b_slope, _) = cb.data;
+        if a == b {
+            continue;
+        }
+
+        // each pair is visited in both orders, measuring along `ca` each time
+        let (la, lb) = (&ca.geom().0, &cb.geom().0);
+        if angle_diff(la, a_slope, lb, b_slope, false) >= angle_tolerance {
+            continue;
+        }
+        if let Some(cmp) = compare_segments(la, lb) {
+            if let Some(portion) = cmp.overlap.filter(|_| cmp.distance <= distance_tolerance) {
+                let (s, e) = (param(la, portion.start), param(la, portion.end));
+                let entry = covered
+                    .entry((a, b, k))
+                    .or_insert_with(|| (*la, Vec::new()));
+                entry.1.push((s.min(e), s.max(e)));
+            }
+        }
+    }
+
+    // the shared length of the lower then higher feature of each pair
+    let mut shared: BTreeMap<(usize, usize), (f64, f64)> = BTreeMap::new();
+    for ((a, b, _), (line, intervals)) in covered {
+        let len = line.length::<Euclidean>() * union_len(intervals);
+        if a < b {
+            shared.entry((a, b)).or_default().0 += len;
+        } else {
+            shared.entry((b, a)).or_default().1 += len;
+        }
+    }
+
+    let schema = Schema::new(vec![
+        Field::new("feature_a", DataType::Int32, false),
+        Field::new("feature_b", DataType::Int32, false),
+        Field::new("shared_len_a", DataType::Float64, false),
+        Field::new("shared_len_b", DataType::Float64, false),
+        Field::new("coverage_a", DataType::Float64, false),
+        Field::new("coverage_b", DataType::Float64, false),
+    ]);
+    let coverage = |len: f64, shared_len: f64| if len > 0.0 { shared_len / len } else { 0.0 };
+
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(Int32Array::from_iter_values(
+                shared.keys().map(|(a, _)| *a as i32),
+            )),
+            Arc::new(Int32Array::from_iter_values(
+                shared.keys().map(|(_, b)| *b as i32),
+            )),
+            Arc::new(Float64Array::from_iter_values(
+                shared.values().map(|(sa, _)| *sa),
+            )),
+            Arc::new(Float64Array::from_iter_values(
+                shared.values().map(|(_, sb)| *sb),
+            )),
+            Arc::new(Float64Array::from_iter_values(
+                shared
+                    .iter()
+                    .map(|((a, _), (sa, _))| coverage(lens[*a], *sa)),
+            )),
+            Arc::new(Float64Array::from_iter_values(
+                shared
+                    .iter()
+                    .map(|((_, b), (_, sb))| coverage(lens[*b], *sb)),
+            )),
+        ],
+    )
+    .expect("All arrays should be identical lengths")
+}
+
+// the total length of the union of parameter intervals
+fn union_len(mut intervals: Vec<(f64, f64)>) -> f64 {
+    intervals.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut total = 0.0;
+    let mut t = 0.0;
+    for (s, e) in intervals {
+        let s = f64::max(s, t);
+        if e > s {
+            total += e - s;
+            t = e;
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::AsArray;
+    use arrow::datatypes::{Float64Type, Int32Type};
+    use geo_types::{coord, LineString};
+
+    #[test]
+    fn test_self_matches() {
+        let lines = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+            // a reversed duplicate digitised slightly differently
+            LineString::new(vec![
+                coord! {x: 10.0, y: 0.1},
+                coord! {x: 5.0, y: 0.1},
+                coord! {x: 0.0, y: 0.1},
+            ]),
+            // overlaps the second half of the first feature
+            LineString::new(vec![coord! {x: 5.0, y: -0.2}, coord! {x: 25.0, y: -0.2}]),
+            // unrelated
+            LineString::new(vec![coord! {x: 0.0, y: 50.0}, coord! {x: 10.0, y: 50.0}]),
+        ];
+        let res = self_matches(lines.into_iter(), 0.5, 5.0);
+
+        let a = res.column(0).as_primitive::<Int32Type>();
+        let b = res.column(1).as_primitive::<Int32Type>();
+        let pairs = a
+            .values()
+            .iter()
+            .zip(b.values().iter())
+            .map(|(a, b)| (*a, *b))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, vec![(0, 1), (0, 2), (1, 2)]);
+
+        let coverage_a = res.column(4).as_primitive::<Float64Type>();
+        let coverage_b = res.column(5).as_primitive::<Float64Type>();
+        assert!((coverage_a.value(0) - 1.0).abs() < 1e-9);
+        assert!((coverage_b.value(0) - 1.0).abs() < 1e-9);
+        assert!((coverage_a.value(1) - 0.5).abs() < 1e-9);
+        assert!((coverage_b.value(1) - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_self_matches_hairpin() {
+        let lines = vec![
+            LineString::new(vec![
+                coord! {x: 0.0, y: 0.0},
+                coord! {x: 10.0, y: 0.0},
+                coord! {x: 0.0, y: 0.4},
+            ]),
+            LineString::new(vec![coord! {x: 0.0, y: 0.2}, coord! {x: 10.0, y: 0.2}]),
+        ];
+        let res = self_matches(lines.into_iter(), 0.5, 5.0);
+        assert_eq!(res.num_rows(), 1);
+
+        let shared_a = res.column(2).as_primitive::<Float64Type>();
+        let shared_b = res.column(3).as_primitive::<Float64Type>();
+        let coverage_a = res.column(4).as_primitive::<Float64Type>();
+        let coverage_b = res.column(5).as_primitive::<Float64Type>();
+        // both legs of the hairpin run along the whole of `b`
+        assert!(shared_a.value(0) > 20.0);
+        assert!((shared_b.value(0) - 10.0).abs() < 1e-9);
+        assert!((coverage_a.value(0) - 1.0).abs() < 1e-9);
+        assert!((coverage_b.value(0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_self_matches_empty() {
+        let res = self_matches(std::iter::empty(), 1.0, 5.0);
+        assert_eq!(res.num_rows(), 0);
+    }
+}
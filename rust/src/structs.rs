@@ -1,7 +1,7 @@
 use geo::{BoundingRect, Distance, Euclidean};
 use geo::{Line, Point};
-use rstar::RTreeObject;
 use rstar::AABB;
+use rstar::{PointDistance, RTreeObject};
 
 // Currently unused
 // enum CrsType {
@@ -46,6 +46,17 @@ impl RTreeObject for TarLine {
     }
 }
 
+impl PointDistance for TarLine {
+    /// Squared euclidean distance from the contained `Line` to a point
+    ///
+    /// The padding is ignored so nearest neighbor queries measure
+    /// distance to the line itself.
+    fn distance_2(&self, point: &Point) -> f64 {
+        let d = Euclidean::distance(&self.0, point);
+        d * d
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,7 +123,7 @@ mod tests {
     fn test_tarline_distance() {
         let tarline = TarLine(
             Line::new(coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}),
-            1.0
+            1.0,
         );
         let other = Line::new(coord! {x: 0.0, y: 3.0}, coord! {x: 10.0, y: 3.0});
 
@@ -121,4 +132,15 @@ mod tests {
         // Parallel horizontal lines 3 units apart
         assert_eq!(distance, 3.0);
     }
+
+    #[test]
+    fn test_tarline_point_distance_ignores_padding() {
+        let tarline = TarLine(
+            Line::new(coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}),
+            5.0,
+        );
+
+        assert_eq!(tarline.distance_2(&Point::new(5.0, 2.0)), 4.0);
+        assert_eq!(tarline.distance_2(&Point::new(13.0, 4.0)), 25.0);
+    }
 }
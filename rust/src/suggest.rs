@@ -0,0 +1,278 @@
+use crate::{angle_diff, Anime, AnimeError};
+use arrow::array::{Float64Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use geo::{Distance, Euclidean, Point};
+use std::sync::Arc;
+
+/// Probabilities reported in the supporting distributions
+pub const SUGGEST_PROBS: [f64; 11] = [0.0, 0.05, 0.1, 0.25, 0.5, 0.75, 0.9, 0.95, 0.975, 0.99, 1.0];
+
+/// Tolerances estimated from the source and target geometries
+///
+/// The quantiles are stored as `(probability, value)` pairs using
+/// the probabilities in [`SUGGEST_PROBS`].
+#[derive(Debug, Clone)]
+pub struct ToleranceSuggestion {
+    /// The recommended `distance_tolerance`
+    pub distance_tolerance: f64,
+    /// The recommended `angle_tolerance` in degrees
+    pub angle_tolerance: f64,
+    /// The number of source segments sampled
+    pub n_sampled: usize,
+    /// The number of sampled segments with a near-parallel target segment
+    pub n_found: usize,
+    /// Quantiles of the distance to the nearest near-parallel target segment
+    pub offset_quantiles: Vec<(f64, f64)>,
+    /// Quantiles of the angle difference to the nearest near-parallel target segment
+    pub angle_quantiles: Vec<(f64, f64)>,
+}
+
+impl ToleranceSuggestion {
+    /// The supporting distributions as a `RecordBatch`
+    ///
+    /// Contains the columns `quantile`, `offset`, and `angle_diff`.
+    pub fn quantiles(&self) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("quantile", DataType::Float64, false),
+            Field::new("offset", DataType::Float64, false),
+            Field::new("angle_diff", DataType::Float64, false),
+        ]);
+
+        let probs = self.offset_quantiles.iter().map(|(p, _)| *p);
+        let offsets = self.offset_quantiles.iter().map(|(_, v)| *v);
+        let angles = self.angle_quantiles.iter().map(|(_, v)| *v);
+
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Float64Array::from_iter_values(probs)),
+                Arc::new(Float64Array::from_iter_values(offsets)),
+                Arc::new(Float64Array::from_iter_values(angles)),
+            ],
+        )
+        .expect("All arrays should be identical lengths")
+    }
+}
+
+impl Anime {
+    /// Suggest distance and angle tolerances from the data
+    ///
+    /// Up to `n_samples` source segments are sampled evenly from the source tree.
+    /// For each, the target tree is searched outward from the segment's midpoint
+    /// for the nearest target segment whose angle differs by less than `max_angle`.
+    /// Targets further than `search_distance` are not considered.
+    ///
+    /// The recommended tolerances are the `quantile` of the resulting lateral
+    /// offset and angle difference distributions. A `quantile` of `0.95`
+    /// accommodates 95% of the sampled segments. Angles are only matched when
+    /// strictly less than the tolerance, so the recommended `angle_tolerance`
+    /// is the next representable value above the quantile. Angles are
+    /// compared as bearings when `self.directed` is set.
+    ///
+    /// Only the geometries are required so this can be called on an `Anime`
+    /// created with [`Anime::load_geometries`] before matching.
+    pub fn suggest_tolerances(
+        &self,
+        n_samples: usize,
+        search_distance: f64,
+        max_angle: f64,
+        quantile: f64,
+    ) -> Result<ToleranceSuggestion, AnimeError> {
+        let n_segments = self.source_tree.size();
+        let step = n_segments.div_ceil(n_samples.max(1)).max(1);
+        let max_dist_2 = search_distance * search_distance;
+
+        let mut n_sampled = 0;
+        let mut offsets = Vec::new();
+        let mut angles = Vec::new();
+
+        for cx in self.source_tree.iter().step_by(step) {
+            n_sampled += 1;
+            let (_, x_slope, _) = cx.data;
+            let x = cx.geom();
+            let midpoint = Point::from((x.start + x.end) / 2.0);
+
+            let nearest = self
+                .target_tree
+                .nearest_neighbor_iter_with_distance_2(&midpoint)
+                .take_while(|(_, d2)| *d2 <= max_dist_2)
+                .map(|(cy, _)| {
+                    let diff = angle_diff(x, x_slope, &cy.geom().0, cy.data.1, self.directed);
+                    (cy, diff)
+                })
+                .find(|(_, angle_diff)| *angle_diff < max_angle);
+
+            if let Some((cy, angle_diff)) = nearest {
+                offsets.push(Euclidean::distance(&cy.geom().0, &**x));
+                angles.push(angle_diff);
+            }
+        }
+
+        if offsets.is_empty() {
+            return Err(AnimeError::NoCandidatesFound);
+        }
+
+        offsets.sort_by(f64::total_cmp);
+        angles.sort_by(f64::total_cmp);
+
+        let offset_quantiles = SUGGEST_PROBS
+            .iter()
+            .map(|p| (*p, sorted_quantile(&offsets, *p)))
+            .collect();
+        let angle_quantiles = SUGGEST_PROBS
+            .iter()
+            .map(|p| (*p, sorted_quantile(&angles, *p)))
+            .collect();
+
+        Ok(ToleranceSuggestion {
+            distance_tolerance: sorted_quantile(&offsets, quantile),
+            angle_tolerance: sorted_quantile(&angles, quantile).next_up(),
+            n_sampled,
+            n_found: offsets.len(),
+            offset_quantiles,
+            angle_quantiles,
+        })
+    }
+}
+
+// linearly interpolated quantile of a sorted, non-empty slice
+fn sorted_quantile(x: &[f64], p: f64) -> f64 {
+    let h = (x.len() - 1) as f64 * p.clamp(0.0, 1.0);
+    let lo = h.floor() as usize;
+    let hi = h.ceil() as usize;
+    x[lo] + (h - lo as f64) * (x[hi] - x[lo])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::{coord, LineString};
+
+    // sources are offset from their targets by 0.5, 1.0, 1.5, and 2.0
+    fn create_offset_lines() -> (Vec<LineString>, Vec<LineString>) {
+        let source = (1..=4)
+            .map(|i| {
+                let y = i as f64 * 10.0 + i as f64 * 0.5;
+                LineString::new(vec![coord! {x: 0.0, y: y}, coord! {x: 10.0, y: y}])
+            })
+            .collect::<Vec<_>>();
+        let target = (1..=4)
+            .map(|i| {
+                let y = i as f64 * 10.0;
+                LineString::new(vec![coord! {x: 0.0, y: y}, coord! {x: 10.0, y: y}])
+            })
+            .collect::<Vec<_>>();
+        (source, target)
+    }
+
+    fn create_offset_anime() -> Anime {
+        let (source, target) = create_offset_lines();
+        Anime::load_geometries(source.into_iter(), target.into_iter(), 1.0, 5.0)
+    }
+
+    #[test]
+    fn test_sorted_quantile() {
+        let x = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(sorted_quantile(&x, 0.0), 1.0);
+        assert_eq!(sorted_quantile(&x, 0.5), 3.0);
+        assert_eq!(sorted_quantile(&x, 0.875), 4.5);
+        assert_eq!(sorted_quantile(&x, 1.0), 5.0);
+    }
+
+    #[test]
+    fn test_suggest_tolerances() {
+        let anime = create_offset_anime();
+        let res = anime.suggest_tolerances(100, 5.0, 20.0, 1.0).unwrap();
+
+        assert_eq!(res.n_sampled, 4);
+        assert_eq!(res.n_found, 4);
+        assert_eq!(res.distance_tolerance, 2.0);
+        assert!(res.angle_tolerance > 0.0);
+        assert_eq!(res.offset_quantiles.len(), SUGGEST_PROBS.len());
+        assert_eq!(res.offset_quantiles[0], (0.0, 0.5));
+    }
+
+    #[test]
+    fn test_suggested_tolerances_match_samples() {
+        // perfectly parallel data has an angle quantile of zero
+        let anime = create_offset_anime();
+        let res = anime.suggest_tolerances(100, 5.0, 20.0, 1.0).unwrap();
+        assert_eq!(res.angle_quantiles.last().unwrap().1, 0.0);
+
+        let (source, target) = create_offset_lines();
+        let anime = Anime::new(
+            source.into_iter(),
+            target.into_iter(),
+            res.distance_tolerance,
+            res.angle_tolerance,
+        );
+        let matches = anime.matches.get().unwrap();
+        for j in 0..4 {
+            assert!(matches[&j].iter().any(|mc| mc.source_index == j));
+        }
+    }
+
+    #[test]
+    fn test_suggest_tolerances_directed() {
+        // a source digitised against its target's direction
+        let source = vec![LineString::new(vec![
+            coord! {x: 10.0, y: 0.5},
+            coord! {x: 0.0, y: 0.5},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let mut anime = Anime::load_geometries(source.into_iter(), target.into_iter(), 1.0, 5.0);
+        assert!(anime.suggest_tolerances(10, 5.0, 20.0, 1.0).is_ok());
+
+        anime.directed = true;
+        let res = anime.suggest_tolerances(10, 5.0, 20.0, 1.0);
+        assert!(matches!(res, Err(AnimeError::NoCandidatesFound)));
+    }
+
+    #[test]
+    fn test_suggest_tolerances_search_distance() {
+        let anime = create_offset_anime();
+        let res = anime.suggest_tolerances(100, 1.2, 20.0, 1.0).unwrap();
+
+        // the sources offset by 1.5 and 2.0 are beyond the search distance
+        assert_eq!(res.n_found, 2);
+        assert_eq!(res.distance_tolerance, 1.0);
+    }
+
+    #[test]
+    fn test_suggest_tolerances_skips_non_parallel() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target = vec![
+            // perpendicular and closest
+            LineString::new(vec![coord! {x: 5.0, y: -5.0}, coord! {x: 5.0, y: 5.0}]),
+            // parallel but further away
+            LineString::new(vec![coord! {x: 0.0, y: 3.0}, coord! {x: 10.0, y: 3.0}]),
+        ];
+        let anime = Anime::load_geometries(source.into_iter(), target.into_iter(), 1.0, 5.0);
+
+        let res = anime.suggest_tolerances(10, 10.0, 20.0, 0.5).unwrap();
+        assert_eq!(res.distance_tolerance, 3.0);
+    }
+
+    #[test]
+    fn test_suggest_tolerances_none_found() {
+        let anime = create_offset_anime();
+        let res = anime.suggest_tolerances(100, 0.1, 20.0, 0.95);
+        assert!(matches!(res, Err(AnimeError::NoCandidatesFound)));
+    }
+
+    #[test]
+    fn test_suggestion_quantiles_batch() {
+        let anime = create_offset_anime();
+        let res = anime.suggest_tolerances(100, 5.0, 20.0, 0.95).unwrap();
+        let batch = res.quantiles();
+
+        assert_eq!(batch.num_rows(), SUGGEST_PROBS.len());
+        assert_eq!(batch.num_columns(), 3);
+    }
+}
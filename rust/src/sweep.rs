@@ -0,0 +1,233 @@
+use crate::{angle_diff, compare_segments, repad_target_tree, Anime, AnimeError};
+use arrow::array::{Float64Array, Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+impl Anime {
+    /// Evaluate a grid of distance and angle tolerances
+    ///
+    /// Candidate segment pairs are found in a single pass using the widest
+    /// distance and angle tolerance. Each combination of `distances` and
+    /// `angles` is then evaluated by filtering those candidates. If the
+    /// widest distance exceeds `self.distance_tolerance`, the target tree is
+    /// rebuilt with the widest distance as its padding first. Angles are
+    /// compared as bearings when `self.directed` is set.
+    ///
+    /// A grid of global tolerances cannot be compared with per-feature
+    /// tolerances so [`AnimeError::FeatureTolerancesUnsupported`] is returned
+    /// when `self.feature_tolerances` is set.
+    ///
+    /// The returned `RecordBatch` has one row per combination with columns:
+    /// - `distance_tolerance` and `angle_tolerance`: the combination evaluated
+    /// - `n_matches`: the number of (source, target) pairs matched
+    /// - `shared_len`: the total shared length of all matches
+    /// - `source_coverage`: the proportion of the total source length matched
+    /// - `target_coverage`: the proportion of the total target length matched
+    ///
+    /// Coverage caps the shared length of each feature at its own length.
+    pub fn sweep(&self, distances: &[f64], angles: &[f64]) -> Result<RecordBatch, AnimeError> {
+        if self.feature_tolerances.is_some() {
+            return Err(AnimeError::FeatureTolerancesUnsupported);
+        }
+
+        let max_dist = distances.iter().copied().fold(0.0, f64::max);
+        let max_angle = angles.iter().copied().fold(0.0, f64::max);
+
+        let repadded;
+        let target_tree = if max_dist > self.distance_tolerance {
+            repadded = repad_target_tree(&self.target_tree, max_dist);
+            &repadded
+        } else {
+            &self.target_tree
+        };
+
+        // single pass over the candidates at the widest tolerance
+        let pairs = self
+            .source_tree
+            .intersection_candidates_with_other_tree(target_tree)
+            .filter_map(|(cx, cy)| {
+                let (i, x_slope, _) = cx.data;
+                let (j, y_slope, _) = cy.data;
+                let angle_diff =
+                    angle_diff(cx.geom(), x_slope, &cy.geom().0, y_slope, self.directed);
+                if angle_diff >= max_angle {
+                    return None;
+                }
+                let cmp = compare_segments(cx.geom(), &cy.geom().0)?;
+                if cmp.distance > max_dist {
+                    return None;
+                }
+                Some((i, j, angle_diff, cmp))
+            })
+            .collect::<Vec<_>>();
+
+        let total_source_len: f64 = self.source_lens.iter().sum();
+        let total_target_len: f64 = self.target_lens.iter().sum();
+
+        let n = distances.len() * angles.len();
+        let mut distance_res = Float64Array::builder(n);
+        let mut angle_res = Float64Array::builder(n);
+        let mut n_matches_res = Int32Array::builder(n);
+        let mut shared_len_res = Float64Array::builder(n);
+        let mut source_coverage_res = Float64Array::builder(n);
+        let mut target_coverage_res = Float64Array::builder(n);
+
+        for &distance_tolerance in distances {
+            for &angle_tolerance in angles {
+                // shared length keyed by (target, source)
+                let mut shared: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+                for (i, j, angle_diff, cmp) in &pairs {
+                    if *angle_diff < angle_tolerance && cmp.distance <= distance_tolerance {
+                        *shared.entry((*j, *i)).or_default() += cmp.shared_len;
+                    }
+                }
+
+                let mut source_shared = vec![0.0; self.source_lens.len()];
+                let mut target_shared = vec![0.0; self.target_lens.len()];
+                for ((j, i), sl) in shared.iter() {
+                    source_shared[*i] += sl;
+                    target_shared[*j] += sl;
+                }
+
+                distance_res.append_value(distance_tolerance);
+                angle_res.append_value(angle_tolerance);
+                n_matches_res.append_value(shared.len() as i32);
+                shared_len_res.append_value(shared.values().sum());
+                source_coverage_res.append_value(coverage(
+                    &source_shared,
+                    &self.source_lens,
+                    total_source_len,
+                ));
+                target_coverage_res.append_value(coverage(
+                    &target_shared,
+                    &self.target_lens,
+                    total_target_len,
+                ));
+            }
+        }
+
+        let schema = Schema::new(vec![
+            Field::new("distance_tolerance", DataType::Float64, false),
+            Field::new("angle_tolerance", DataType::Float64, false),
+            Field::new("n_matches", DataType::Int32, false),
+            Field::new("shared_len", DataType::Float64, false),
+            Field::new("source_coverage", DataType::Float64, false),
+            Field::new("target_coverage", DataType::Float64, false),
+        ]);
+
+        let res = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(distance_res.finish()),
+                Arc::new(angle_res.finish()),
+                Arc::new(n_matches_res.finish()),
+                Arc::new(shared_len_res.finish()),
+                Arc::new(source_coverage_res.finish()),
+                Arc::new(target_coverage_res.finish()),
+            ],
+        )
+        .expect("All arrays should be identical lengths");
+        Ok(res)
+    }
+}
+
+// proportion of the total length covered with each feature capped at its length
+fn coverage(shared: &[f64], lens: &[f64], total_len: f64) -> f64 {
+    if total_len <= 0.0 {
+        return 0.0;
+    }
+    let covered: f64 = shared.iter().zip(lens).map(|(s, l)| s.min(*l)).sum();
+    covered / total_len
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tolerance::FeatureTolerances;
+    use crate::{Anime, AnimeError};
+    use arrow::array::{AsArray, RecordBatch};
+    use arrow::datatypes::{Float64Type, Int32Type};
+    use geo_types::{coord, LineString};
+
+    fn create_test_anime(distance_tolerance: f64) -> Anime {
+        let source = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: 3.0}, coord! {x: 10.0, y: 5.0}]),
+        ];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.5},
+            coord! {x: 10.0, y: 0.5},
+        ])];
+
+        Anime::load_geometries(
+            source.into_iter(),
+            target.into_iter(),
+            distance_tolerance,
+            5.0,
+        )
+    }
+
+    fn n_matches(res: &RecordBatch) -> Vec<i32> {
+        res.column(2).as_primitive::<Int32Type>().values().to_vec()
+    }
+
+    #[test]
+    fn test_sweep_grid_shape() {
+        let anime = create_test_anime(1.0);
+        let res = anime.sweep(&[0.1, 1.0, 5.0], &[1.0, 15.0]).unwrap();
+
+        assert_eq!(res.num_rows(), 6);
+        assert_eq!(res.num_columns(), 6);
+
+        let distances = res.column(0).as_primitive::<Float64Type>();
+        let angles = res.column(1).as_primitive::<Float64Type>();
+        assert_eq!(distances.value(0), 0.1);
+        assert_eq!(angles.value(0), 1.0);
+        assert_eq!(distances.value(5), 5.0);
+        assert_eq!(angles.value(5), 15.0);
+    }
+
+    #[test]
+    fn test_sweep_monotonic_in_tolerance() {
+        let anime = create_test_anime(1.0);
+        let res = anime.sweep(&[0.1, 1.0, 5.0], &[1.0, 15.0]).unwrap();
+
+        // (0.1, _) matches nothing, (1.0, _) matches the parallel source,
+        // and the sloped source only matches with both wide tolerances
+        assert_eq!(n_matches(&res), vec![0, 0, 1, 1, 1, 2]);
+
+        let coverage = res.column(4).as_primitive::<Float64Type>();
+        assert_eq!(coverage.value(0), 0.0);
+        assert!(coverage.value(5) > coverage.value(2));
+        assert!(coverage.value(5) <= 1.0);
+    }
+
+    #[test]
+    fn test_sweep_wider_than_index() {
+        // the target tree is padded by 0.1 so wider distances require repadding
+        let anime = create_test_anime(0.1);
+        let res = anime.sweep(&[1.0], &[5.0]).unwrap();
+
+        assert_eq!(n_matches(&res), vec![1]);
+        let target_coverage = res.column(5).as_primitive::<Float64Type>();
+        assert_eq!(target_coverage.value(0), 1.0);
+    }
+
+    #[test]
+    fn test_sweep_empty_grid() {
+        let anime = create_test_anime(1.0);
+        let res = anime.sweep(&[], &[5.0]).unwrap();
+        assert_eq!(res.num_rows(), 0);
+    }
+
+    #[test]
+    fn test_sweep_feature_tolerances() {
+        let mut anime = create_test_anime(1.0);
+        anime.feature_tolerances = Some(FeatureTolerances {
+            source_distance: Some(vec![1.0, 2.0]),
+            ..Default::default()
+        });
+        let res = anime.sweep(&[1.0], &[5.0]);
+        assert!(matches!(res, Err(AnimeError::FeatureTolerancesUnsupported)));
+    }
+}
@@ -0,0 +1,364 @@
+use arrow::array::{Int32Array, RecordBatch};
+use arrow::datatypes::{DataType, Field, Schema};
+use geo::{BoundingRect, Euclidean, Length, Simplify};
+use geo_types::{Coord, LineString};
+use std::sync::Arc;
+
+/// Perturbations applied when generating a synthetic network
+///
+/// Probabilities are applied independently to each feature. Distances
+/// are in the units of the input geometries. The default applies no
+/// perturbation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Perturbation {
+    /// Seed of the random number generator
+    pub seed: u64,
+    /// Features are shifted perpendicular to their overall direction by
+    /// a uniform random distance of up to `offset` either side
+    pub offset: f64,
+    /// Vertices are moved by up to `jitter` along each axis
+    pub jitter: f64,
+    /// Tolerance used to simplify each feature. `0.0` disables simplification
+    pub simplify: f64,
+    /// Probability a feature is merged with the next feature when the
+    /// next feature starts where it ends
+    pub merge_prob: f64,
+    /// Probability a feature is split in two
+    pub split_prob: f64,
+    /// Probability a feature's digitisation is reversed
+    pub reverse_prob: f64,
+    /// Probability a feature is dropped
+    pub drop_prob: f64,
+    /// Probability a spurious feature, with no true match, is added
+    /// for each input feature
+    pub add_prob: f64,
+}
+
+/// A perturbed copy of a network with its true matches
+#[derive(Debug, Clone)]
+pub struct PerturbedNetwork {
+    /// The perturbed features
+    pub lines: Vec<LineString>,
+    /// The true matches with columns `source_id`, the index of the input
+    /// feature, and `target_id`, the index of the perturbed feature
+    pub truth: RecordBatch,
+}
+
+/// Generate a perturbed copy of `lines` with known ground truth
+///
+/// Perturbations are applied in the order: drop, merge, simplify, split,
+/// reverse, offset, jitter, and add. The result is deterministic for a
+/// given `seed`. Using `lines` as the source and the perturbed lines as
+/// the target, `truth` can be passed to [`crate::evaluate::evaluate`].
+pub fn perturb(lines: &[LineString], params: &Perturbation) -> PerturbedNetwork {
+    let mut rng = SplitMix64::new(params.seed);
+
+    // each feature is stored with the indices of the input features it came from
+    let mut features = lines
+        .iter()
+        .enumerate()
+        .filter(|_| !rng.chance(params.drop_prob))
+        .map(|(i, l)| (vec![i], l.clone()))
+        .collect::<Vec<_>>();
+
+    features = merge_features(features, params.merge_prob, &mut rng);
+
+    if params.simplify > 0.0 {
+        for (_, l) in features.iter_mut() {
+            *l = l.simplify(&params.simplify);
+        }
+    }
+
+    features = features
+        .into_iter()
+        .flat_map(|(origin, l)| {
+            if rng.chance(params.split_prob) {
+                if let Some((a, b)) = split_line(&l, rng.uniform(0.25, 0.75)) {
+                    return vec![(origin.clone(), a), (origin, b)];
+                }
+            }
+            vec![(origin, l)]
+        })
+        .collect();
+
+    for (_, l) in features.iter_mut() {
+        if rng.chance(params.reverse_prob) {
+            l.0.reverse();
+        }
+        if params.offset > 0.0 {
+            shift_line(l, rng.uniform(-params.offset, params.offset));
+        }
+        if params.jitter > 0.0 {
+            for c in l.0.iter_mut() {
+                c.x += rng.uniform(-params.jitter, params.jitter);
+                c.y += rng.uniform(-params.jitter, params.jitter);
+            }
+        }
+    }
+
+    // spurious features are random lines within the extent of the input
+    if params.add_prob > 0.0 {
+        if let Some(rect) = geo_types::MultiLineString::new(lines.to_vec()).bounding_rect() {
+            for l in lines.iter() {
+                if !rng.chance(params.add_prob) {
+                    continue;
+                }
+                let len = l.length::<Euclidean>();
+                let start = Coord {
+                    x: rng.uniform(rect.min().x, rect.max().x),
+                    y: rng.uniform(rect.min().y, rect.max().y),
+                };
+                let theta = rng.uniform(0.0, std::f64::consts::TAU);
+                let end = start + Coord::from((len * theta.cos(), len * theta.sin()));
+                features.push((vec![], LineString::new(vec![start, end])));
+            }
+        }
+    }
+
+    let (source_ids, target_ids): (Vec<i32>, Vec<i32>) = features
+        .iter()
+        .enumerate()
+        .flat_map(|(j, (origin, _))| origin.iter().map(move |i| (*i as i32, j as i32)))
+        .unzip();
+
+    let schema = Schema::new(vec![
+        Field::new("source_id", DataType::Int32, false),
+        Field::new("target_id", DataType::Int32, false),
+    ]);
+    let truth = RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(Int32Array::from(source_ids)),
+            Arc::new(Int32Array::from(target_ids)),
+        ],
+    )
+    .expect("All arrays should be identical lengths");
+
+    PerturbedNetwork {
+        lines: features.into_iter().map(|(_, l)| l).collect(),
+        truth,
+    }
+}
+
+fn merge_features(
+    features: Vec<(Vec<usize>, LineString)>,
+    merge_prob: f64,
+    rng: &mut SplitMix64,
+) -> Vec<(Vec<usize>, LineString)> {
+    let mut res: Vec<(Vec<usize>, LineString)> = Vec::with_capacity(features.len());
+    for (origin, l) in features {
+        if let Some((prev_origin, prev)) = res.last_mut() {
+            let touches = match (prev.0.last(), l.0.first()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            };
+            if touches && rng.chance(merge_prob) {
+                prev_origin.extend(origin);
+                prev.0.extend(l.0.into_iter().skip(1));
+                continue;
+            }
+        }
+        res.push((origin, l));
+    }
+    res
+}
+
+/// Split a line at `fraction` of its length
+fn split_line(l: &LineString, fraction: f64) -> Option<(LineString, LineString)> {
+    let total = l.length::<Euclidean>();
+    if total <= 0.0 {
+        return None;
+    }
+    let target = total * fraction;
+
+    let mut walked = 0.0;
+    for (k, li) in l.lines().enumerate() {
+        let len = li.length::<Euclidean>();
+        if len > 0.0 && walked + len >= target {
+            let t = (target - walked) / len;
+            let split = li.start + li.delta() * t;
+            let mut first = l.0[..=k].to_vec();
+            first.push(split);
+            let mut second = vec![split];
+            second.extend_from_slice(&l.0[k + 1..]);
+            return Some((LineString::new(first), LineString::new(second)));
+        }
+        walked += len;
+    }
+    None
+}
+
+/// Shift a line perpendicular to the direction from its start to its end
+fn shift_line(l: &mut LineString, distance: f64) {
+    let (Some(start), Some(end)) = (l.0.first(), l.0.last()) else {
+        return;
+    };
+    let d = *end - *start;
+    let len = d.x.hypot(d.y);
+    if len <= 0.0 {
+        return;
+    }
+    let normal = Coord::from((-d.y / len * distance, d.x / len * distance));
+    for c in l.0.iter_mut() {
+        *c = *c + normal;
+    }
+}
+
+/// A small, fast, seeded pseudo random number generator
+///
+/// Using SplitMix64 keeps generated networks reproducible without
+/// an external dependency.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn uniform(&mut self, lo: f64, hi: f64) -> f64 {
+        lo + (hi - lo) * self.next_f64()
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        p > 0.0 && self.next_f64() < p
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate;
+    use crate::Anime;
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int32Type;
+    use geo_types::coord;
+
+    // a grid of horizontal features, each touching the next
+    fn create_lines() -> Vec<LineString> {
+        (0..10)
+            .map(|i| {
+                let x = i as f64 * 10.0;
+                LineString::new(vec![
+                    coord! {x: x, y: 0.0},
+                    coord! {x: x + 5.0, y: 0.0},
+                    coord! {x: x + 10.0, y: 0.0},
+                ])
+            })
+            .collect()
+    }
+
+    fn truth_pairs(truth: &RecordBatch) -> Vec<(i32, i32)> {
+        let s = truth.column(0).as_primitive::<Int32Type>();
+        let t = truth.column(1).as_primitive::<Int32Type>();
+        s.values()
+            .iter()
+            .copied()
+            .zip(t.values().iter().copied())
+            .collect()
+    }
+
+    #[test]
+    fn test_no_perturbation() {
+        let lines = create_lines();
+        let res = perturb(&lines, &Perturbation::default());
+        assert_eq!(res.lines, lines);
+        assert_eq!(
+            truth_pairs(&res.truth),
+            (0..10).map(|i| (i, i)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_drop_all() {
+        let params = Perturbation {
+            drop_prob: 1.0,
+            ..Default::default()
+        };
+        let res = perturb(&create_lines(), &params);
+        assert!(res.lines.is_empty());
+        assert_eq!(res.truth.num_rows(), 0);
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        let params = Perturbation {
+            split_prob: 1.0,
+            ..Default::default()
+        };
+        let res = perturb(&create_lines(), &params);
+        assert_eq!(res.lines.len(), 20);
+        assert_eq!(truth_pairs(&res.truth)[..2], [(0, 0), (0, 1)]);
+        let len = |l: &LineString| l.length::<Euclidean>();
+        assert!((len(&res.lines[0]) + len(&res.lines[1]) - 10.0).abs() < 1e-9);
+
+        let params = Perturbation {
+            merge_prob: 1.0,
+            ..Default::default()
+        };
+        let res = perturb(&create_lines(), &params);
+        assert_eq!(res.lines.len(), 1);
+        assert_eq!(res.truth.num_rows(), 10);
+    }
+
+    #[test]
+    fn test_reverse_and_simplify() {
+        let params = Perturbation {
+            reverse_prob: 1.0,
+            simplify: 0.1,
+            ..Default::default()
+        };
+        let res = perturb(&create_lines(), &params);
+        assert_eq!(
+            res.lines[0],
+            LineString::new(vec![coord! {x: 10.0, y: 0.0}, coord! {x: 0.0, y: 0.0}])
+        );
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let params = Perturbation {
+            seed: 42,
+            offset: 1.0,
+            jitter: 0.5,
+            split_prob: 0.5,
+            add_prob: 0.5,
+            ..Default::default()
+        };
+        let a = perturb(&create_lines(), &params);
+        let b = perturb(&create_lines(), &params);
+        assert_eq!(a.lines, b.lines);
+        assert_eq!(truth_pairs(&a.truth), truth_pairs(&b.truth));
+    }
+
+    #[test]
+    fn test_perturbed_matches_recovered() {
+        let lines = create_lines();
+        let params = Perturbation {
+            seed: 7,
+            offset: 0.5,
+            jitter: 0.05,
+            split_prob: 0.3,
+            reverse_prob: 0.5,
+            ..Default::default()
+        };
+        let res = perturb(&lines, &params);
+
+        let mut anime = Anime::load_geometries(lines.into_iter(), res.lines.into_iter(), 1.0, 10.0);
+        anime.find_matches().unwrap();
+        let eval = evaluate(&anime, &res.truth).unwrap();
+        assert_eq!(eval.recall, 1.0);
+    }
+}
@@ -0,0 +1,579 @@
+use crate::{
+    compare_components, insert_match, prepare_lines, AnimeError, ComponentData, LoadOptions,
+    MatchesMap, SourceTree, TarLine, TargetTree,
+};
+use geo::BoundingRect;
+use geo_types::{coord, Line};
+use rstar::primitives::{CachedEnvelope, GeomWithData};
+use rstar::{RTree, RTreeObject};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// number of components buffered per tile before spilling to disk
+const SPILL_THRESHOLD: usize = 4096;
+// number of components buffered across all tiles before the largest
+// buffers are spilled to disk
+const SPILL_BUDGET: usize = 1 << 20;
+// bytes in a serialised component record
+const RECORD_LEN: usize = 56;
+// distinguishes the spill directories of tile stores within a process
+static SPILL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Options for matching in square tiles
+///
+/// See [`match_tiled`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileOptions {
+    /// The width and height of each tile. Must be finite and positive.
+    pub tile_size: f64,
+    pub distance_tolerance: f64,
+    pub angle_tolerance: f64,
+    /// A directory where component lines are written while the inputs are
+    /// streamed. Each call writes to its own subdirectory which is removed
+    /// afterwards. When `None` they are kept in memory.
+    pub spill_dir: Option<PathBuf>,
+}
+
+/// The merged result of tiled matching
+#[derive(Debug, Clone)]
+pub struct TiledMatches {
+    pub source_lens: Vec<f64>,
+    pub target_lens: Vec<f64>,
+    pub matches: MatchesMap,
+    /// The number of tiles containing both source and target lines
+    pub n_tiles: usize,
+}
+
+/// Match networks too large to index at once
+///
+/// The source and target are each streamed once, from an iterator such as
+/// a file reader, and their component lines are bucketed into square tiles
+/// of `tile_size`. A source component line is placed in every tile its
+/// envelope intersects and a target component line in every tile its
+/// envelope, padded by `distance_tolerance`, intersects. The tiles
+/// therefore overlap by the tolerance and each is matched independently
+/// with two small R* Trees.
+///
+/// A pair of component lines found in several tiles is only counted in the
+/// tile containing the lower left corner of the intersection of their
+/// envelopes, so the merged matches are identical to those of
+/// [`crate::Anime::new`].
+///
+/// With a `spill_dir`, only one tile and a bounded number of buffered
+/// component lines are held in memory at once. Each tile's buffer is
+/// spilled once it is full and, when the buffers of all tiles together
+/// exceed the budget, the largest are spilled until half of it is free.
+/// Spilled files are written to a new subdirectory of `spill_dir`, so
+/// concurrent calls sharing a `spill_dir` never read each other's files.
+/// The files are removed once their tile is matched and the subdirectory
+/// once matching finishes or fails.
+///
+/// Returns [`AnimeError::InvalidTileSize`] unless `tile_size` is finite
+/// and positive.
+pub fn match_tiled(
+    source: impl Iterator<Item = geo_types::LineString>,
+    target: impl Iterator<Item = geo_types::LineString>,
+    options: &TileOptions,
+) -> Result<TiledMatches, AnimeError> {
+    let is_valid = options.tile_size.is_finite() && options.tile_size > 0.0;
+    if !is_valid {
+        return Err(AnimeError::InvalidTileSize(options.tile_size));
+    }
+
+    let mut source_tiles = TileStore::new(options, "source")?;
+    let mut target_tiles = TileStore::new(options, "target")?;
+
+    let source_lens = bucket(source, &mut source_tiles, options.tile_size, 0.0)?;
+    let target_lens = bucket(
+        target,
+        &mut target_tiles,
+        options.tile_size,
+        options.distance_tolerance,
+    )?;
+
+    let keys = source_tiles
+        .keys()
+        .intersection(&target_tiles.keys())
+        .copied()
+        .collect::<Vec<_>>();
+
+    let mut matches = MatchesMap::new();
+    for key in keys.iter() {
+        let source_tree: SourceTree = RTree::bulk_load(
+            source_tiles
+                .take(key)?
+                .into_iter()
+                .map(|(line, data)| GeomWithData::new(CachedEnvelope::new(line), data))
+                .collect(),
+        );
+        let target_tree: TargetTree = RTree::bulk_load(
+            target_tiles
+                .take(key)?
+                .into_iter()
+                .map(|(line, data)| {
+                    let tl = TarLine(line, options.distance_tolerance);
+                    GeomWithData::new(CachedEnvelope::new(tl), data)
+                })
+                .collect(),
+        );
+
+        for (cx, cy) in source_tree.intersection_candidates_with_other_tree(&target_tree) {
+            let (ex, ey) = (cx.envelope(), cy.envelope());
+            let corner = (
+                f64::max(ex.lower().x(), ey.lower().x()),
+                f64::max(ex.lower().y(), ey.lower().y()),
+            );
+            if tile_of(corner.0, corner.1, options.tile_size) != *key {
+                continue;
+            }
+            if let Some((cmp, _)) = compare_components(
+                cx.geom(),
+                cx.data,
+                &cy.geom().0,
+                cy.data,
+                options.angle_tolerance,
+                options.distance_tolerance,
+                false,
+            ) {
+                insert_match(&mut matches, cx.data.0, cy.data.0, &cmp, None);
+            }
+        }
+    }
+    source_tiles.clear()?;
+    target_tiles.clear()?;
+
+    Ok(TiledMatches {
+        source_lens,
+        target_lens,
+        matches,
+        n_tiles: keys.len(),
+    })
+}
+
+type TileKey = (i64, i64);
+type Component = (Line, ComponentData);
+
+fn tile_of(x: f64, y: f64, tile_size: f64) -> TileKey {
+    (
+        (x / tile_size).floor() as i64,
+        (y / tile_size).floor() as i64,
+    )
+}
+
+// add each component line to the tiles it intersects and return the lengths
+fn bucket(
+    lines: impl Iterator<Item = geo_types::LineString>,
+    tiles: &mut TileStore,
+    tile_size: f64,
+    padding: f64,
+) -> Result<Vec<f64>, AnimeError> {
+    let options = LoadOptions::default();
+    let mut lens = Vec::new();
+    for (i, xi) in lines.enumerate() {
+        let (len, components) = prepare_lines(xi, &options);
+        lens.push(len);
+        for (line, slope, k) in components {
+            let bb = line.bounding_rect();
+            let (x0, y0) = tile_of(bb.min().x - padding, bb.min().y - padding, tile_size);
+            let (x1, y1) = tile_of(bb.max().x + padding, bb.max().y + padding, tile_size);
+            for tx in x0..=x1 {
+                for ty in y0..=y1 {
+                    tiles.push((tx, ty), (line, (i, slope, k)))?;
+                }
+            }
+        }
+    }
+    tiles.flush()?;
+    Ok(lens)
+}
+
+// component lines bucketed by tile, optionally spilled to disk
+struct TileStore {
+    buffers: BTreeMap<TileKey, Vec<Component>>,
+    // the number of component lines in `buffers`
+    n_buffered: usize,
+    // the largest `n_buffered` before spilling
+    budget: usize,
+    spilled: BTreeSet<TileKey>,
+    // a subdirectory of `TileOptions::spill_dir` owned by this store
+    spill_dir: Option<PathBuf>,
+    prefix: &'static str,
+}
+
+impl TileStore {
+    fn new(options: &TileOptions, prefix: &'static str) -> Result<Self, AnimeError> {
+        let spill_dir = match options.spill_dir.as_ref() {
+            Some(dir) => Some(Self::create_spill_dir(dir, prefix)?),
+            None => None,
+        };
+        Ok(Self {
+            buffers: BTreeMap::new(),
+            n_buffered: 0,
+            budget: SPILL_BUDGET,
+            spilled: BTreeSet::new(),
+            spill_dir,
+            prefix,
+        })
+    }
+
+    // create a subdirectory that no other store, in this or a previous
+    // process, is using
+    fn create_spill_dir(dir: &Path, prefix: &str) -> Result<PathBuf, AnimeError> {
+        fs::create_dir_all(dir).map_err(io_error)?;
+        loop {
+            let n = SPILL_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let sub = dir.join(format!("anime-{prefix}-{}-{n}", std::process::id()));
+            match fs::create_dir(&sub) {
+                Ok(()) => return Ok(sub),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(io_error(e)),
+            }
+        }
+    }
+
+    fn path(dir: &Path, prefix: &str, key: &TileKey) -> PathBuf {
+        dir.join(format!("{prefix}_{}_{}.bin", key.0, key.1))
+    }
+
+    fn keys(&self) -> BTreeSet<TileKey> {
+        self.buffers
+            .keys()
+            .chain(self.spilled.iter())
+            .copied()
+            .collect()
+    }
+
+    fn push(&mut self, key: TileKey, component: Component) -> Result<(), AnimeError> {
+        let buffer = self.buffers.entry(key).or_default();
+        buffer.push(component);
+        self.n_buffered += 1;
+        if self.spill_dir.is_some() && buffer.len() >= SPILL_THRESHOLD {
+            self.spill(key)?;
+        }
+        if self.spill_dir.is_some() && self.n_buffered > self.budget {
+            self.spill_largest()?;
+        }
+        Ok(())
+    }
+
+    // spill the largest buffers until half of the budget is free
+    fn spill_largest(&mut self) -> Result<(), AnimeError> {
+        let mut sizes = self
+            .buffers
+            .iter()
+            .map(|(key, buffer)| (buffer.len(), *key))
+            .collect::<Vec<_>>();
+        sizes.sort_by_key(|(len, _)| std::cmp::Reverse(*len));
+        for (_, key) in sizes {
+            if self.n_buffered <= self.budget / 2 {
+                break;
+            }
+            self.spill(key)?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self, key: TileKey) -> Result<(), AnimeError> {
+        let Some(dir) = self.spill_dir.as_ref() else {
+            return Ok(());
+        };
+        let buffer = self.buffers.remove(&key).unwrap_or_default();
+        self.n_buffered -= buffer.len();
+        let mut bytes = Vec::with_capacity(buffer.len() * RECORD_LEN);
+        for (line, (i, slope, k)) in buffer {
+            bytes.extend((i as u64).to_le_bytes());
+            bytes.extend((k as u64).to_le_bytes());
+            for v in [slope, line.start.x, line.start.y, line.end.x, line.end.y] {
+                bytes.extend(v.to_le_bytes());
+            }
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::path(dir, self.prefix, &key))
+            .and_then(|mut f| f.write_all(&bytes))
+            .map_err(io_error)?;
+        self.spilled.insert(key);
+        Ok(())
+    }
+
+    // spill every buffered tile when a directory is set
+    fn flush(&mut self) -> Result<(), AnimeError> {
+        if self.spill_dir.is_some() {
+            let keys = self.buffers.keys().copied().collect::<Vec<_>>();
+            for key in keys {
+                self.spill(key)?;
+            }
+        }
+        Ok(())
+    }
+
+    // remove and return the component lines of a tile
+    fn take(&mut self, key: &TileKey) -> Result<Vec<Component>, AnimeError> {
+        let mut res = self.buffers.remove(key).unwrap_or_default();
+        self.n_buffered -= res.len();
+        if let (Some(dir), true) = (self.spill_dir.as_ref(), self.spilled.remove(key)) {
+            let path = Self::path(dir, self.prefix, key);
+            let bytes = fs::read(&path).map_err(io_error)?;
+            fs::remove_file(&path).map_err(io_error)?;
+            res.extend(bytes.chunks_exact(RECORD_LEN).map(|r| {
+                let u = |k: usize| u64::from_le_bytes(r[k..k + 8].try_into().unwrap());
+                let f = |k: usize| f64::from_le_bytes(r[k..k + 8].try_into().unwrap());
+                let line = Line::new(coord! {x: f(24), y: f(32)}, coord! {x: f(40), y: f(48)});
+                (line, (u(0) as usize, f(16), u(8) as usize))
+            }));
+        }
+        Ok(res)
+    }
+
+    // remove any tiles that were never matched
+    fn clear(&mut self) -> Result<(), AnimeError> {
+        self.buffers.clear();
+        self.n_buffered = 0;
+        if let Some(dir) = self.spill_dir.as_ref() {
+            for key in std::mem::take(&mut self.spilled) {
+                fs::remove_file(Self::path(dir, self.prefix, &key)).map_err(io_error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// remove the spill directory and any files left behind by an early return
+impl Drop for TileStore {
+    fn drop(&mut self) {
+        if let Some(dir) = self.spill_dir.as_ref() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+fn io_error(e: std::io::Error) -> AnimeError {
+    AnimeError::Io(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Anime;
+    use geo_types::LineString;
+
+    // a grid of streets and a copy offset and split at different points
+    fn networks() -> (Vec<LineString>, Vec<LineString>) {
+        let mut source = Vec::new();
+        let mut target = Vec::new();
+        for k in 0..5 {
+            let c = k as f64 * 10.0;
+            source.push(LineString::new(vec![
+                coord! {x: 0.0, y: c},
+                coord! {x: 23.0, y: c},
+                coord! {x: 45.0, y: c},
+            ]));
+            source.push(LineString::new(vec![
+                coord! {x: c, y: 0.0},
+                coord! {x: c, y: 45.0},
+            ]));
+            target.push(LineString::new(vec![
+                coord! {x: 0.0, y: c + 0.3},
+                coord! {x: 45.0, y: c + 0.3},
+            ]));
+            target.push(LineString::new(vec![
+                coord! {x: c - 0.3, y: 0.0},
+                coord! {x: c - 0.3, y: 17.0},
+            ]));
+            target.push(LineString::new(vec![
+                coord! {x: c - 0.3, y: 17.0},
+                coord! {x: c - 0.3, y: 45.0},
+            ]));
+        }
+        (source, target)
+    }
+
+    fn shared(matches: &MatchesMap) -> Vec<(usize, usize, f64)> {
+        let mut res = matches
+            .iter()
+            .flat_map(|(j, items)| {
+                items
+                    .iter()
+                    .map(move |mc| (mc.source_index, *j, mc.shared_len))
+            })
+            .collect::<Vec<_>>();
+        res.sort_by_key(|p| (p.0, p.1));
+        res
+    }
+
+    fn assert_same(res: &TiledMatches, expected: &Anime) {
+        let (a, b) = (
+            shared(&res.matches),
+            shared(expected.matches.get().unwrap()),
+        );
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert_eq!((x.0, x.1), (y.0, y.1));
+            assert!((x.2 - y.2).abs() < 1e-9);
+        }
+        assert_eq!(res.source_lens, expected.source_lens);
+        assert_eq!(res.target_lens, expected.target_lens);
+    }
+
+    #[test]
+    fn test_match_tiled() {
+        let (source, target) = networks();
+        let expected = Anime::new(
+            source.clone().into_iter(),
+            target.clone().into_iter(),
+            1.0,
+            5.0,
+        );
+        let options = TileOptions {
+            tile_size: 7.0,
+            distance_tolerance: 1.0,
+            angle_tolerance: 5.0,
+            spill_dir: None,
+        };
+        let res = match_tiled(source.into_iter(), target.into_iter(), &options).unwrap();
+
+        assert!(res.n_tiles > 1);
+        assert_same(&res, &expected);
+    }
+
+    #[test]
+    fn test_match_tiled_spilled() {
+        let (source, target) = networks();
+        let expected = Anime::new(
+            source.clone().into_iter(),
+            target.clone().into_iter(),
+            1.0,
+            5.0,
+        );
+        let dir = std::env::temp_dir().join(format!("anime-tiles-{}", std::process::id()));
+        let options = TileOptions {
+            tile_size: 12.5,
+            distance_tolerance: 1.0,
+            angle_tolerance: 5.0,
+            spill_dir: Some(dir.clone()),
+        };
+        let res = match_tiled(source.into_iter(), target.into_iter(), &options).unwrap();
+
+        assert_same(&res, &expected);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_spill_over_budget() {
+        let dir = std::env::temp_dir().join(format!("anime-budget-{}", std::process::id()));
+        let options = TileOptions {
+            tile_size: 1.0,
+            distance_tolerance: 1.0,
+            angle_tolerance: 5.0,
+            spill_dir: Some(dir.clone()),
+        };
+        let mut tiles = TileStore::new(&options, "source").unwrap();
+        tiles.budget = 4;
+
+        let component = |i: usize| {
+            let line = Line::new(coord! {x: 0.0, y: 0.0}, coord! {x: 1.0, y: 0.0});
+            (line, (i, 0.0, 0))
+        };
+        for i in 0..3 {
+            tiles.push((0, 0), component(i)).unwrap();
+        }
+        tiles.push((1, 0), component(3)).unwrap();
+        assert!(tiles.spilled.is_empty());
+
+        // exceeding the budget spills the largest buffer before the stream ends
+        tiles.push((2, 0), component(4)).unwrap();
+        assert_eq!(tiles.spilled, BTreeSet::from([(0, 0)]));
+        assert_eq!(tiles.n_buffered, 2);
+        let spill_dir = tiles.spill_dir.clone().unwrap();
+        assert_eq!(fs::read_dir(&spill_dir).unwrap().count(), 1);
+
+        let taken = tiles.take(&(0, 0)).unwrap();
+        assert_eq!(
+            taken.iter().map(|c| c.1 .0).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        tiles.clear().unwrap();
+        assert_eq!(fs::read_dir(&spill_dir).unwrap().count(), 0);
+        drop(tiles);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_spilled_removed_on_drop() {
+        let dir = std::env::temp_dir().join(format!("anime-drop-{}", std::process::id()));
+        let options = TileOptions {
+            tile_size: 1.0,
+            distance_tolerance: 1.0,
+            angle_tolerance: 5.0,
+            spill_dir: Some(dir.clone()),
+        };
+        let mut tiles = TileStore::new(&options, "target").unwrap();
+        let line = Line::new(coord! {x: 0.0, y: 0.0}, coord! {x: 1.0, y: 0.0});
+        tiles.push((0, 0), (line, (0, 0.0, 0))).unwrap();
+        tiles.flush().unwrap();
+        let spill_dir = tiles.spill_dir.clone().unwrap();
+        assert_eq!(fs::read_dir(&spill_dir).unwrap().count(), 1);
+
+        drop(tiles);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_spill_dirs_are_separate() {
+        let dir = std::env::temp_dir().join(format!("anime-separate-{}", std::process::id()));
+        let options = TileOptions {
+            tile_size: 1.0,
+            distance_tolerance: 1.0,
+            angle_tolerance: 5.0,
+            spill_dir: Some(dir.clone()),
+        };
+        // a stale file from an earlier run is never read
+        fs::create_dir_all(&dir).unwrap();
+        let stale = TileStore::path(&dir, "source", &(0, 0));
+        fs::write(&stale, [0u8; RECORD_LEN]).unwrap();
+
+        let line = Line::new(coord! {x: 0.0, y: 0.0}, coord! {x: 1.0, y: 0.0});
+        let mut a = TileStore::new(&options, "source").unwrap();
+        let mut b = TileStore::new(&options, "source").unwrap();
+        assert_ne!(a.spill_dir, b.spill_dir);
+        for (i, tiles) in [&mut a, &mut b].into_iter().enumerate() {
+            tiles.push((0, 0), (line, (i, 0.0, 0))).unwrap();
+            tiles.flush().unwrap();
+        }
+        for (i, tiles) in [&mut a, &mut b].into_iter().enumerate() {
+            let taken = tiles.take(&(0, 0)).unwrap();
+            assert_eq!(taken.iter().map(|c| c.1 .0).collect::<Vec<_>>(), vec![i]);
+        }
+
+        drop(a);
+        drop(b);
+        fs::remove_file(&stale).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_tile_size() {
+        let (source, target) = networks();
+        for tile_size in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let options = TileOptions {
+                tile_size,
+                distance_tolerance: 1.0,
+                angle_tolerance: 5.0,
+                spill_dir: None,
+            };
+            let res = match_tiled(
+                source.clone().into_iter(),
+                target.clone().into_iter(),
+                &options,
+            );
+            assert!(matches!(res, Err(AnimeError::InvalidTileSize(_))));
+        }
+    }
+}
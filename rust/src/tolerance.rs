@@ -0,0 +1,194 @@
+/// How per-feature tolerances are combined for a source and target pair
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CombineTolerance {
+    /// Use the larger of the two tolerances
+    #[default]
+    Max,
+    /// Use the smaller of the two tolerances
+    Min,
+    /// Add the two tolerances together
+    Sum,
+}
+
+impl CombineTolerance {
+    /// Combine a source and target tolerance
+    pub fn combine(&self, source: f64, target: f64) -> f64 {
+        match self {
+            CombineTolerance::Max => source.max(target),
+            CombineTolerance::Min => source.min(target),
+            CombineTolerance::Sum => source + target,
+        }
+    }
+}
+
+/// Per-feature distance and angle tolerances
+///
+/// Each vector, when present, must have one value per source or target
+/// geometry. The effective tolerance for a pair is determined by:
+///
+/// - both sides present: the values are combined with `combine`
+/// - one side present: that side's value is used
+/// - neither side present: the global `Anime` tolerance is used
+///
+/// Because `combine` is monotonic, each target is padded by the largest
+/// effective distance tolerance it can have with any source.
+#[derive(Debug, Clone, Default)]
+pub struct FeatureTolerances {
+    pub source_distance: Option<Vec<f64>>,
+    pub source_angle: Option<Vec<f64>>,
+    pub target_distance: Option<Vec<f64>>,
+    pub target_angle: Option<Vec<f64>>,
+    pub combine: CombineTolerance,
+}
+
+impl FeatureTolerances {
+    /// The effective distance tolerance between source `i` and target `j`
+    pub fn distance(&self, i: usize, j: usize, default: f64) -> f64 {
+        self.resolve(&self.source_distance, &self.target_distance, i, j, default)
+    }
+
+    /// The effective angle tolerance between source `i` and target `j`
+    pub fn angle(&self, i: usize, j: usize, default: f64) -> f64 {
+        self.resolve(&self.source_angle, &self.target_angle, i, j, default)
+    }
+
+    /// The largest effective distance tolerance of each target
+    ///
+    /// Returns a function of the target index which is used as the
+    /// padding of the target's `TarLine`s.
+    pub fn target_padding(&self, default: f64) -> impl Fn(usize) -> f64 + '_ {
+        let source_max = self
+            .source_distance
+            .as_ref()
+            .map(|v| v.iter().copied().fold(f64::NEG_INFINITY, f64::max));
+        move |j| {
+            let target = self
+                .target_distance
+                .as_ref()
+                .and_then(|v| v.get(j).copied());
+            match (source_max, target) {
+                (Some(s), Some(t)) => self.combine.combine(s, t),
+                (Some(s), None) => s,
+                (None, Some(t)) => t,
+                (None, None) => default,
+            }
+        }
+    }
+
+    /// Check that each tolerance vector has one value per geometry
+    pub(crate) fn is_valid(&self, n_source: usize, n_target: usize) -> bool {
+        self.is_valid_source(n_source) && self.is_valid_target(n_target)
+    }
+
+    /// Check that each source tolerance vector has one value per source
+    pub(crate) fn is_valid_source(&self, n_source: usize) -> bool {
+        check_len(&self.source_distance, n_source) && check_len(&self.source_angle, n_source)
+    }
+
+    /// Check that each target tolerance vector has one value per target
+    pub(crate) fn is_valid_target(&self, n_target: usize) -> bool {
+        check_len(&self.target_distance, n_target) && check_len(&self.target_angle, n_target)
+    }
+
+    fn resolve(
+        &self,
+        source: &Option<Vec<f64>>,
+        target: &Option<Vec<f64>>,
+        i: usize,
+        j: usize,
+        default: f64,
+    ) -> f64 {
+        // a missing value is treated like a missing vector
+        let s = source.as_ref().and_then(|v| v.get(i).copied());
+        let t = target.as_ref().and_then(|v| v.get(j).copied());
+        match (s, t) {
+            (Some(s), Some(t)) => self.combine.combine(s, t),
+            (Some(s), None) => s,
+            (None, Some(t)) => t,
+            (None, None) => default,
+        }
+    }
+}
+
+fn check_len(v: &Option<Vec<f64>>, n: usize) -> bool {
+    v.as_ref().is_none_or(|v| v.len() == n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_tolerances(combine: CombineTolerance) -> FeatureTolerances {
+        FeatureTolerances {
+            source_distance: Some(vec![1.0, 4.0]),
+            source_angle: None,
+            target_distance: Some(vec![2.0, 3.0, 10.0]),
+            target_angle: Some(vec![5.0, 10.0, 15.0]),
+            combine,
+        }
+    }
+
+    #[test]
+    fn test_combine_tolerance() {
+        assert_eq!(CombineTolerance::Max.combine(1.0, 2.0), 2.0);
+        assert_eq!(CombineTolerance::Min.combine(1.0, 2.0), 1.0);
+        assert_eq!(CombineTolerance::Sum.combine(1.0, 2.0), 3.0);
+    }
+
+    #[test]
+    fn test_resolve_distance() {
+        let tols = create_tolerances(CombineTolerance::Max);
+        assert_eq!(tols.distance(0, 0, 99.0), 2.0);
+        assert_eq!(tols.distance(1, 0, 99.0), 4.0);
+
+        let tols = create_tolerances(CombineTolerance::Min);
+        assert_eq!(tols.distance(1, 1, 99.0), 3.0);
+
+        let tols = create_tolerances(CombineTolerance::Sum);
+        assert_eq!(tols.distance(1, 2, 99.0), 14.0);
+    }
+
+    #[test]
+    fn test_resolve_single_side_and_default() {
+        let tols = create_tolerances(CombineTolerance::Max);
+        // only targets have angle tolerances
+        assert_eq!(tols.angle(0, 2, 99.0), 15.0);
+
+        let tols = FeatureTolerances::default();
+        assert_eq!(tols.angle(0, 2, 99.0), 99.0);
+        assert_eq!(tols.target_padding(99.0)(0), 99.0);
+    }
+
+    #[test]
+    fn test_target_padding() {
+        let tols = create_tolerances(CombineTolerance::Max);
+        let padding = tols.target_padding(99.0);
+        assert_eq!(padding(0), 4.0);
+        assert_eq!(padding(2), 10.0);
+
+        let tols = create_tolerances(CombineTolerance::Min);
+        assert_eq!(tols.target_padding(99.0)(2), 4.0);
+
+        let tols = create_tolerances(CombineTolerance::Sum);
+        assert_eq!(tols.target_padding(99.0)(0), 6.0);
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let tols = create_tolerances(CombineTolerance::Max);
+        assert!(tols.is_valid(2, 3));
+        assert!(!tols.is_valid(3, 3));
+        assert!(!tols.is_valid(2, 2));
+        assert!(tols.is_valid_source(2));
+        assert!(!tols.is_valid_target(2));
+    }
+
+    #[test]
+    fn test_resolve_out_of_range() {
+        let tols = create_tolerances(CombineTolerance::Max);
+        // source 5 has no value so the target's is used
+        assert_eq!(tols.distance(5, 1, 99.0), 3.0);
+        assert_eq!(tols.distance(5, 7, 99.0), 99.0);
+        assert_eq!(tols.angle(0, 7, 99.0), 99.0);
+    }
+}
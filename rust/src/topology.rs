@@ -0,0 +1,212 @@
+use crate::nodes::Junction;
+use crate::{Anime, AnimeError, MatchCandidate, MatchesMap};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Options for the topology-aware disambiguation pass
+///
+/// See [`Anime::disambiguate`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TopologyFilter {
+    /// End points within this distance of each other are connected
+    pub snap: f64,
+    /// Matches with less support than this are dropped
+    pub min_support: f64,
+    /// When `false`, no matches are dropped and every match is instead
+    /// down-weighted by multiplying its `shared_len` and `aligned_len` by
+    /// its support. The `segments` describe the geometry of the match so
+    /// they are kept as is and their lengths no longer sum to `shared_len`.
+    pub drop: bool,
+}
+
+impl Anime {
+    /// The topological support of each match
+    ///
+    /// Connectivity graphs are built for the source and target where two
+    /// linestrings are neighbours when they share a junction. A match
+    /// between source `i` and target `j` is supported by each neighbour of
+    /// `i` that is matched to `j` or one of its neighbours, and by each
+    /// neighbour of `j` that is matched to `i` or one of its neighbours.
+    ///
+    /// The support is the mean of the supported share of neighbours on each
+    /// side, between 0 and 1. A side without neighbours offers no evidence
+    /// and is ignored. A match where neither side has neighbours has a
+    /// support of `1.0`.
+    pub fn topology_support(&self, snap: f64) -> Result<BTreeMap<(usize, usize), f64>, AnimeError> {
+        let inner = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+        let pairs = inner
+            .iter()
+            .flat_map(|(j, items)| items.iter().map(move |mc| (mc.source_index, *j)))
+            .collect::<BTreeSet<_>>();
+
+        let (source_junctions, target_junctions) = self.junctions(snap);
+        let source_nbrs = neighbours(&source_junctions, self.source_lens.len());
+        let target_nbrs = neighbours(&target_junctions, self.target_lens.len());
+
+        let support = pairs
+            .iter()
+            .map(|(i, j)| {
+                let source_side =
+                    supported_share(&source_nbrs[*i], *j, &target_nbrs[*j], |a, b| {
+                        pairs.contains(&(a, b))
+                    });
+                let target_side =
+                    supported_share(&target_nbrs[*j], *i, &source_nbrs[*i], |b, a| {
+                        pairs.contains(&(a, b))
+                    });
+                let sides = [source_side, target_side]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>();
+                let support = if sides.is_empty() {
+                    1.0
+                } else {
+                    sides.iter().sum::<f64>() / sides.len() as f64
+                };
+                ((*i, *j), support)
+            })
+            .collect();
+        Ok(support)
+    }
+
+    /// Drop or down-weight matches unsupported by their neighbours
+    ///
+    /// Spurious matches often occur near junctions where short features pick
+    /// up shared length from crossing roads. Such matches are rarely
+    /// supported by the surrounding network. The support of each match is
+    /// computed with [`Anime::topology_support`] using `self.matches` as the
+    /// initial hypothesis.
+    pub fn disambiguate(&self, options: &TopologyFilter) -> Result<MatchesMap, AnimeError> {
+        let inner = self.matches.get().ok_or(AnimeError::MatchesNotFound)?;
+        let support = self.topology_support(options.snap)?;
+
+        let mut res = MatchesMap::new();
+        for (j, items) in inner.iter() {
+            let kept = items
+                .iter()
+                .filter_map(|mc| {
+                    let s = support[&(mc.source_index, *j)];
+                    if options.drop {
+                        (s >= options.min_support).then(|| mc.clone())
+                    } else {
+                        Some(MatchCandidate {
+                            shared_len: mc.shared_len * s,
+                            aligned_len: mc.aligned_len * s,
+                            ..mc.clone()
+                        })
+                    }
+                })
+                .collect::<Vec<_>>();
+            if !kept.is_empty() {
+                res.insert(*j, kept);
+            }
+        }
+        Ok(res)
+    }
+}
+
+// the linestrings sharing a junction with each linestring
+fn neighbours(junctions: &[Junction], n: usize) -> Vec<BTreeSet<usize>> {
+    let mut res = vec![BTreeSet::new(); n];
+    for junction in junctions {
+        for a in junction.edges.iter() {
+            for b in junction.edges.iter().filter(|b| *b != a) {
+                res[*a].insert(*b);
+            }
+        }
+    }
+    res
+}
+
+// the share of `nbrs` matched to `other` or one of `other_nbrs`
+fn supported_share(
+    nbrs: &BTreeSet<usize>,
+    other: usize,
+    other_nbrs: &BTreeSet<usize>,
+    is_match: impl Fn(usize, usize) -> bool,
+) -> Option<f64> {
+    if nbrs.is_empty() {
+        return None;
+    }
+    let n_supported = nbrs
+        .iter()
+        .filter(|a| is_match(**a, other) || other_nbrs.iter().any(|b| is_match(**a, *b)))
+        .count();
+    Some(n_supported as f64 / nbrs.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo_types::{coord, LineString};
+
+    fn create_anime() -> Anime {
+        let source = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 10.0, y: 0.0}, coord! {x: 20.0, y: 0.0}]),
+        ];
+        let target = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.2}, coord! {x: 10.0, y: 0.2}]),
+            LineString::new(vec![coord! {x: 10.0, y: 0.2}, coord! {x: 20.0, y: 0.2}]),
+            // a stub leading to a road absent from the source
+            LineString::new(vec![coord! {x: 4.0, y: 0.8}, coord! {x: 9.0, y: 0.8}]),
+            LineString::new(vec![coord! {x: 9.0, y: 0.8}, coord! {x: 9.0, y: 10.0}]),
+        ];
+        Anime::new(source.into_iter(), target.into_iter(), 1.0, 5.0)
+    }
+
+    #[test]
+    fn test_topology_support() {
+        let anime = create_anime();
+        let support = anime.topology_support(0.01).unwrap();
+
+        assert_eq!(support[&(0, 0)], 1.0);
+        assert_eq!(support[&(1, 1)], 1.0);
+        assert_eq!(support[&(0, 2)], 0.0);
+    }
+
+    #[test]
+    fn test_disambiguate_drop() {
+        let anime = create_anime();
+        let options = TopologyFilter {
+            snap: 0.01,
+            min_support: 0.5,
+            drop: true,
+        };
+        let res = anime.disambiguate(&options).unwrap();
+
+        assert!(!res.contains_key(&2));
+        assert!(res[&0].iter().any(|mc| mc.source_index == 0));
+    }
+
+    #[test]
+    fn test_disambiguate_down_weight() {
+        let anime = create_anime();
+        let options = TopologyFilter {
+            snap: 0.01,
+            min_support: 0.5,
+            drop: false,
+        };
+        let res = anime.disambiguate(&options).unwrap();
+
+        let raw = &anime.matches.get().unwrap()[&0][0];
+        assert_eq!(res[&0][0].shared_len, raw.shared_len);
+        assert_eq!(res[&0][0].aligned_len, raw.aligned_len);
+        assert_eq!(res[&2][0].shared_len, 0.0);
+        assert!(anime.matches.get().unwrap()[&2][0].aligned_len > 0.0);
+        assert_eq!(res[&2][0].aligned_len, 0.0);
+    }
+
+    #[test]
+    fn test_isolated_features_supported() {
+        let source = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+        ])];
+        let target = vec![LineString::new(vec![
+            coord! {x: 0.0, y: 0.5},
+            coord! {x: 10.0, y: 0.5},
+        ])];
+        let anime = Anime::new(source.into_iter(), target.into_iter(), 1.0, 5.0);
+        assert_eq!(anime.topology_support(0.01).unwrap()[&(0, 0)], 1.0);
+    }
+}
@@ -0,0 +1,486 @@
+use arrow::array::{Float64Array, Int32Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use geo::line_intersection::{line_intersection, LineIntersection};
+use geo_types::{Coord, Line, LineString};
+use rstar::primitives::GeomWithData;
+use rstar::{RTree, RTreeObject};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+/// A topology problem that distorts matching
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IssueKind {
+    /// The linestring has no coordinates
+    Empty,
+    /// The linestring has a single coordinate
+    SingleVertex,
+    /// A coordinate is NaN or infinite
+    NonFinite,
+    /// Two consecutive coordinates are identical
+    ZeroLengthSegment,
+    /// Two non-adjacent segments of the linestring intersect
+    SelfIntersection,
+    /// Another linestring has the same coordinates, in either direction
+    Duplicate,
+    /// Another linestring shares a collinear portion
+    Overlap,
+    /// An end point is near, but not coincident with, another end point
+    NearMissEndpoint,
+    /// A dangling end point stops short of another linestring
+    Undershoot,
+    /// A dangling end point extends a short distance past another linestring
+    Overshoot,
+}
+
+impl IssueKind {
+    /// The name used in the validation report
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueKind::Empty => "empty",
+            IssueKind::SingleVertex => "single_vertex",
+            IssueKind::NonFinite => "non_finite",
+            IssueKind::ZeroLengthSegment => "zero_length_segment",
+            IssueKind::SelfIntersection => "self_intersection",
+            IssueKind::Duplicate => "duplicate",
+            IssueKind::Overlap => "overlap",
+            IssueKind::NearMissEndpoint => "near_miss_endpoint",
+            IssueKind::Undershoot => "undershoot",
+            IssueKind::Overshoot => "overshoot",
+        }
+    }
+}
+
+/// A problem found with a single linestring
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    /// The index of the linestring
+    pub feature: usize,
+    pub kind: IssueKind,
+    /// The index of the other linestring involved, if any
+    pub other: Option<usize>,
+    /// Where the problem occurs, if it has a location
+    pub location: Option<Coord>,
+}
+
+type SegmentTree = RTree<GeomWithData<Line, (usize, usize)>>;
+type EndpointTree = RTree<GeomWithData<[f64; 2], usize>>;
+
+/// Find topology problems in a set of linestrings
+///
+/// End points are checked against other linestrings within `tolerance`.
+/// Each end point reports at most one of a near-miss, an overshoot, or an
+/// undershoot, in that order of priority. End points coincident with
+/// another linestring's end point are connected and not reported.
+///
+/// Linestrings that are empty, have a single vertex, or contain non-finite
+/// coordinates are excluded from the remaining checks.
+pub fn find_issues(lines: &[LineString], tolerance: f64) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let issue = |feature, kind, other, location| ValidationIssue {
+        feature,
+        kind,
+        other,
+        location,
+    };
+
+    // features that can be checked geometrically
+    let mut valid = vec![false; lines.len()];
+    for (i, l) in lines.iter().enumerate() {
+        match l.0.len() {
+            0 => issues.push(issue(i, IssueKind::Empty, None, None)),
+            1 => issues.push(issue(i, IssueKind::SingleVertex, None, Some(l.0[0]))),
+            _ => match l.0.iter().find(|c| !(c.x.is_finite() && c.y.is_finite())) {
+                Some(c) => issues.push(issue(i, IssueKind::NonFinite, None, Some(*c))),
+                None => valid[i] = true,
+            },
+        }
+    }
+
+    let segments: SegmentTree = RTree::bulk_load(
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| valid[*i])
+            .flat_map(|(i, l)| {
+                l.lines()
+                    .enumerate()
+                    .map(move |(k, li)| GeomWithData::new(li, (i, k)))
+            })
+            .collect(),
+    );
+    let endpoints: EndpointTree = RTree::bulk_load(
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| valid[*i])
+            .flat_map(|(i, l)| {
+                let (start, end) = (l.0[0], l.0[l.0.len() - 1]);
+                [
+                    GeomWithData::new([start.x, start.y], i),
+                    GeomWithData::new([end.x, end.y], i),
+                ]
+            })
+            .collect(),
+    );
+
+    for (i, l) in lines.iter().enumerate().filter(|(i, _)| valid[*i]) {
+        for li in l.lines().filter(|li| li.start == li.end) {
+            issues.push(issue(i, IssueKind::ZeroLengthSegment, None, Some(li.start)));
+        }
+        if let Some(c) = self_intersection(l) {
+            issues.push(issue(i, IssueKind::SelfIntersection, None, Some(c)));
+        }
+    }
+
+    let duplicates = find_duplicates(lines, &valid);
+    for (i, j) in duplicates.iter() {
+        issues.push(issue(*i, IssueKind::Duplicate, Some(*j), None));
+    }
+    for (i, j, c) in find_overlaps(&segments) {
+        if !duplicates.contains(&(i, j)) {
+            issues.push(issue(i, IssueKind::Overlap, Some(j), Some(c)));
+        }
+    }
+
+    for (i, l) in lines.iter().enumerate().filter(|(i, _)| valid[*i]) {
+        let n = l.0.len();
+        let ends = [
+            (l.0[0], Line::new(l.0[1], l.0[0])),
+            (l.0[n - 1], Line::new(l.0[n - 2], l.0[n - 1])),
+        ];
+        for (end, end_segment) in ends {
+            if let Some((kind, other)) =
+                check_endpoint(i, end, end_segment, &segments, &endpoints, tolerance)
+            {
+                issues.push(issue(i, kind, Some(other), Some(end)));
+            }
+        }
+    }
+
+    issues.sort_by(|a, b| a.feature.cmp(&b.feature).then(a.kind.cmp(&b.kind)));
+    issues
+}
+
+/// Validate a set of linestrings as an Arrow table
+///
+/// Contains one row per problem found by [`find_issues`] with the columns
+/// `feature_id`, `issue`, `other_id`, `x`, and `y`. `other_id`, `x`, and
+/// `y` are null when the problem has no other linestring or location.
+pub fn validate(lines: &[LineString], tolerance: f64) -> RecordBatch {
+    let issues = find_issues(lines, tolerance);
+
+    let schema = Schema::new(vec![
+        Field::new("feature_id", DataType::Int32, false),
+        Field::new("issue", DataType::Utf8, false),
+        Field::new("other_id", DataType::Int32, true),
+        Field::new("x", DataType::Float64, true),
+        Field::new("y", DataType::Float64, true),
+    ]);
+
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(Int32Array::from_iter_values(
+                issues.iter().map(|x| x.feature as i32),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                issues.iter().map(|x| x.kind.as_str()),
+            )),
+            Arc::new(Int32Array::from_iter(
+                issues.iter().map(|x| x.other.map(|o| o as i32)),
+            )),
+            Arc::new(Float64Array::from_iter(
+                issues.iter().map(|x| x.location.map(|c| c.x)),
+            )),
+            Arc::new(Float64Array::from_iter(
+                issues.iter().map(|x| x.location.map(|c| c.y)),
+            )),
+        ],
+    )
+    .expect("All arrays should be identical lengths")
+}
+
+// the first intersection between non-adjacent segments
+//
+// Zero-length segments are reported separately and skipped so the
+// segments either side of one are treated as adjacent. Candidate pairs
+// are found with an R* Tree of the segments.
+fn self_intersection(l: &LineString) -> Option<Coord> {
+    let segments = l
+        .lines()
+        .filter(|li| li.start != li.end)
+        .collect::<Vec<_>>();
+    let n = segments.len();
+    let closed = l.is_closed();
+    let tree: RTree<GeomWithData<Line, usize>> = RTree::bulk_load(
+        segments
+            .iter()
+            .enumerate()
+            .map(|(k, li)| GeomWithData::new(*li, k))
+            .collect(),
+    );
+
+    for (a, la) in segments.iter().enumerate() {
+        let mut candidates = tree
+            .locate_in_envelope_intersecting(&la.envelope())
+            .map(|lb| lb.data)
+            // the first and last segments of a ring share their end points
+            .filter(|b| *b >= a + 2 && !(closed && a == 0 && *b == n - 1))
+            .collect::<Vec<_>>();
+        candidates.sort();
+        for b in candidates {
+            match line_intersection(*la, segments[b]) {
+                Some(LineIntersection::SinglePoint { intersection, .. }) => {
+                    return Some(intersection)
+                }
+                Some(LineIntersection::Collinear { intersection }) => {
+                    return Some(intersection.start)
+                }
+                None => {}
+            }
+        }
+    }
+    None
+}
+
+// pairs of linestrings with identical coordinates in either direction
+fn find_duplicates(lines: &[LineString], valid: &[bool]) -> BTreeSet<(usize, usize)> {
+    let key = |l: &LineString| {
+        let fwd =
+            l.0.iter()
+                .map(|c| (c.x.to_bits(), c.y.to_bits()))
+                .collect::<Vec<_>>();
+        let mut rev = fwd.clone();
+        rev.reverse();
+        fwd.min(rev)
+    };
+
+    let mut groups: BTreeMap<Vec<(u64, u64)>, Vec<usize>> = BTreeMap::new();
+    for (i, l) in lines.iter().enumerate().filter(|(i, _)| valid[*i]) {
+        groups.entry(key(l)).or_default().push(i);
+    }
+
+    let mut res = BTreeSet::new();
+    for group in groups.values() {
+        for a in group {
+            for b in group.iter().filter(|b| *b != a) {
+                res.insert((*a, *b));
+            }
+        }
+    }
+    res
+}
+
+// pairs of linestrings sharing a collinear portion of positive length
+fn find_overlaps(segments: &SegmentTree) -> Vec<(usize, usize, Coord)> {
+    let mut res: BTreeMap<(usize, usize), Coord> = BTreeMap::new();
+    for x in segments.iter() {
+        let (i, _) = x.data;
+        for y in segments.locate_in_envelope_intersecting(&x.envelope()) {
+            let (j, _) = y.data;
+            if i == j || res.contains_key(&(i, j)) {
+                continue;
+            }
+            if let Some(LineIntersection::Collinear { intersection }) =
+                line_intersection(*x.geom(), *y.geom())
+            {
+                if intersection.start != intersection.end {
+                    res.insert((i, j), intersection.start);
+                }
+            }
+        }
+    }
+    res.into_iter().map(|((i, j), c)| (i, j, c)).collect()
+}
+
+fn check_endpoint(
+    i: usize,
+    end: Coord,
+    end_segment: Line,
+    segments: &SegmentTree,
+    endpoints: &EndpointTree,
+    tolerance: f64,
+) -> Option<(IssueKind, usize)> {
+    let pt = [end.x, end.y];
+    let tol_2 = tolerance * tolerance;
+
+    // the other end of a closed linestring connects to this end point
+    let mut n_self = 0;
+    let mut near_miss = None;
+    for (other, d2) in endpoints.nearest_neighbor_iter_with_distance_2(&pt) {
+        if d2 > tol_2 {
+            break;
+        }
+        if d2 == 0.0 {
+            if other.data != i {
+                return None;
+            }
+            n_self += 1;
+        } else if other.data != i && near_miss.is_none() {
+            near_miss = Some(other.data);
+        }
+    }
+    if n_self > 1 {
+        return None;
+    }
+    if let Some(j) = near_miss {
+        return Some((IssueKind::NearMissEndpoint, j));
+    }
+
+    // the end segment crosses another linestring just before its end point
+    for y in segments.locate_in_envelope_intersecting(&end_segment.envelope()) {
+        let (j, _) = y.data;
+        if j == i {
+            continue;
+        }
+        if let Some(LineIntersection::SinglePoint { intersection, .. }) =
+            line_intersection(end_segment, *y.geom())
+        {
+            let d = intersection - end;
+            if intersection != end && d.x.hypot(d.y) <= tolerance {
+                return Some((IssueKind::Overshoot, j));
+            }
+        }
+    }
+
+    // the end point stops short of another linestring
+    let nearest = segments
+        .nearest_neighbor_iter_with_distance_2(&geo_types::Point::from(end))
+        .take_while(|(_, d2)| *d2 <= tol_2)
+        .find(|(y, _)| y.data.0 != i);
+    match nearest {
+        Some((y, d2)) if d2 > 0.0 => Some((IssueKind::Undershoot, y.data.0)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray};
+    use geo_types::coord;
+
+    fn kinds(issues: &[ValidationIssue], feature: usize) -> Vec<IssueKind> {
+        issues
+            .iter()
+            .filter(|x| x.feature == feature)
+            .map(|x| x.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_degenerate_linestrings() {
+        let lines = vec![
+            LineString::new(vec![]),
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 0.0, y: f64::NAN}, coord! {x: 1.0, y: 1.0}]),
+            LineString::new(vec![
+                coord! {x: 5.0, y: 5.0},
+                coord! {x: 5.0, y: 5.0},
+                coord! {x: 6.0, y: 5.0},
+            ]),
+        ];
+        let issues = find_issues(&lines, 0.1);
+        assert_eq!(kinds(&issues, 0), vec![IssueKind::Empty]);
+        assert_eq!(kinds(&issues, 1), vec![IssueKind::SingleVertex]);
+        assert_eq!(kinds(&issues, 2), vec![IssueKind::NonFinite]);
+        assert_eq!(kinds(&issues, 3), vec![IssueKind::ZeroLengthSegment]);
+    }
+
+    #[test]
+    fn test_self_intersection() {
+        let bowtie = LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 10.0},
+            coord! {x: 10.0, y: 0.0},
+            coord! {x: 0.0, y: 10.0},
+        ]);
+        let ring = LineString::new(vec![
+            coord! {x: 20.0, y: 0.0},
+            coord! {x: 30.0, y: 0.0},
+            coord! {x: 30.0, y: 10.0},
+            coord! {x: 20.0, y: 0.0},
+        ]);
+        let issues = find_issues(&[bowtie, ring], 0.1);
+        assert_eq!(kinds(&issues, 0), vec![IssueKind::SelfIntersection]);
+        assert_eq!(issues[0].location, Some(coord! {x: 5.0, y: 5.0}));
+        assert!(kinds(&issues, 1).is_empty());
+    }
+
+    #[test]
+    fn test_zero_length_segment_not_self_intersection() {
+        // a repeated vertex in the middle of a line and at the end of a ring
+        let line = LineString::new(vec![
+            coord! {x: 0.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+            coord! {x: 10.0, y: 0.0},
+            coord! {x: 10.0, y: 10.0},
+        ]);
+        let ring = LineString::new(vec![
+            coord! {x: 20.0, y: 0.0},
+            coord! {x: 30.0, y: 0.0},
+            coord! {x: 30.0, y: 10.0},
+            coord! {x: 20.0, y: 0.0},
+            coord! {x: 20.0, y: 0.0},
+        ]);
+        let issues = find_issues(&[line, ring], 0.1);
+        assert_eq!(kinds(&issues, 0), vec![IssueKind::ZeroLengthSegment]);
+        assert_eq!(kinds(&issues, 1), vec![IssueKind::ZeroLengthSegment]);
+    }
+
+    #[test]
+    fn test_duplicates_and_overlaps() {
+        let lines = vec![
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 10.0, y: 0.0}, coord! {x: 0.0, y: 0.0}]),
+            LineString::new(vec![coord! {x: 5.0, y: 0.0}, coord! {x: 15.0, y: 0.0}]),
+        ];
+        let issues = find_issues(&lines, 0.1);
+        let of = |f: usize, kind: IssueKind| {
+            issues
+                .iter()
+                .filter(|x| x.feature == f && x.kind == kind)
+                .filter_map(|x| x.other)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(of(0, IssueKind::Duplicate), vec![1]);
+        assert_eq!(of(0, IssueKind::Overlap), vec![2]);
+        assert_eq!(of(2, IssueKind::Overlap), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_endpoint_issues() {
+        let lines = vec![
+            // the main road
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 20.0, y: 0.0}]),
+            // stops 0.3 short of the main road
+            LineString::new(vec![coord! {x: 5.0, y: 10.0}, coord! {x: 5.0, y: 0.3}]),
+            // crosses the main road by 0.2
+            LineString::new(vec![coord! {x: 10.0, y: 10.0}, coord! {x: 10.0, y: -0.2}]),
+            // ends 0.1 from the main road's end point
+            LineString::new(vec![coord! {x: 20.1, y: 10.0}, coord! {x: 20.1, y: 0.0}]),
+            // connected properly
+            LineString::new(vec![coord! {x: 0.0, y: 10.0}, coord! {x: 0.0, y: 0.0}]),
+        ];
+        let issues = find_issues(&lines, 0.5);
+        assert_eq!(kinds(&issues, 1), vec![IssueKind::Undershoot]);
+        assert_eq!(kinds(&issues, 2), vec![IssueKind::Overshoot]);
+        assert_eq!(kinds(&issues, 3), vec![IssueKind::NearMissEndpoint]);
+        assert!(kinds(&issues, 4).is_empty());
+        // the main road's end point is near the end of feature 3
+        assert_eq!(kinds(&issues, 0), vec![IssueKind::NearMissEndpoint]);
+    }
+
+    #[test]
+    fn test_validate_table() {
+        let lines = vec![
+            LineString::new(vec![]),
+            LineString::new(vec![coord! {x: 0.0, y: 0.0}, coord! {x: 10.0, y: 0.0}]),
+        ];
+        let res = validate(&lines, 0.1);
+        assert_eq!(res.num_rows(), 1);
+        assert_eq!(res.num_columns(), 5);
+        assert_eq!(res.column(1).as_string::<i32>().value(0), "empty");
+        assert!(res.column(2).is_null(0));
+    }
+}